@@ -30,7 +30,7 @@ impl Plugin for Schedule
 ////////////////////////////////////////////////////////////////////////////////
 
 //text UIのメッセージセクションの型
-type MessageSect<'a> =
+pub type MessageSect<'a> =
 (   &'a str, //表示文字列
     &'a str, //フォントのAssets
     f32,     //フォントのサイズ
@@ -173,7 +173,7 @@ fn spawn_footer
 }
 
 //TextBundleを作る
-fn text_ui
+pub fn text_ui
 (   message: &[ MessageSect ],
     asset_svr: &Res<AssetServer>,
 ) -> TextBundle
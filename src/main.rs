@@ -1,11 +1,14 @@
 //external crates
 use bevy::
 {   prelude::*,
+    app::AppExit,
     log::LogPlugin,
     core_pipeline::clear_color::ClearColorConfig,
     window::WindowMode,
     asset::LoadState,
     render::camera,
+    render::view::RenderLayers,
+    render::render_resource::{ Extent3d, TextureDimension, TextureFormat },
     diagnostic::DiagnosticsStore,
     diagnostic::FrameTimeDiagnosticsPlugin,
     input::mouse,
@@ -13,14 +16,18 @@ use bevy::
     utils::HashSet,
     sprite::Anchor,
     sprite::MaterialMesh2dBundle,
+    audio::Volume,
 };
 use once_cell::sync::Lazy;
 use counted_array::counted_array;
 use rand::prelude::*;
 use regex::Regex;
+use serde::{ Serialize, Deserialize };
 
 //standard library
 use std::ops::{ Range, Add, AddAssign };
+use std::collections::VecDeque;
+use std::time::Duration;
 use std::f32::consts::{ PI, TAU, FRAC_PI_2 };
 
 //internal submodules
@@ -29,6 +36,9 @@ use public::*;
 
 mod load_assets;
 mod init_app;
+mod title;
+mod settings;
+mod sound;
 mod play_game;
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -68,6 +78,8 @@ fn main()
             misc::spawn_3d_camera, //3D camera
             misc::spawn_3d_light,  //3D light
 
+            debug::spawn_free_fly_camera.run_if( misc::DEBUG ), //debug用フリーカメラ
+
             //テスト用：オブジェクト表示
             (   debug::spawn_2d_sprites, //2D表示テスト
                 debug::spawn_3d_objects, //3D表示テスト
@@ -79,16 +91,42 @@ fn main()
     .add_systems
     (   Update,
         //テスト用：3Dカメラを極座標上で動かす
-        (   (   debug::catch_input_keyboard, //キー入力
+        (   (   debug::catch_input_keyboard //キー入力(矢印キーはPlayerやメニューの操作と重なるので、
+                    .run_if( misc::DEBUG ) //debug時のデフォルトカメラ限定。タイトルと設定画面は除く)
+                    .run_if( any_with_component::<misc::AppDefault3dCamera>() )
+                    .run_if( not( in_state( MyState::Title ).or_else( in_state( MyState::Settings ) ) ) ),
                 debug::catch_input_mouse,    //マウス
                 debug::catch_input_gamepad,  //ゲームパッド
             ),
-            debug::move_orbit_camera::<Camera3d> //カメラの移動
+            debug::move_orbit_camera::<misc::AppDefault3dCamera> //カメラの移動
                 .run_if( any_with_component::<misc::AppDefault3dCamera>() ),
         )
         .chain() //実行順の固定
         // .run_if( misc::DEBUG )
     )
+    .add_systems
+    (   Update,
+        //debug用：フリーカメラ（Stateに関係なく使える）
+        (   debug::toggle_free_fly_camera, //[F2]キーで切替
+            debug::move_free_fly_camera,   //移動と見回し
+        )
+        .chain()
+        .run_if( misc::DEBUG )
+    )
+    ;
+
+    //メニュー画面の操作（タイトル・設定・ポーズ・ゲームオーバーで共通）
+    app
+    .add_event::<menu::MenuSelected>()
+    .add_systems
+    (   Update,
+        (   menu::move_cursor, //[↑][↓]キーでカーソル移動
+            menu::confirm,     //[Enter]キーで決定
+            menu::update_text, //表示の更新
+        )
+        .chain()
+        .run_if( any_with_component::<menu::Menu>() )
+    )
     ;
 
     //メイン処理
@@ -96,6 +134,9 @@ fn main()
     .add_state::<MyState>() //Stateを初期化する。enumの#[default]で初期値指定
     .add_plugins( load_assets::Schedule ) //assetsの事前ロード
     .add_plugins( init_app::Schedule )    //ゲーム枠・FPSの表示等、事前処理
+    .add_plugins( title::Schedule )       //タイトル画面
+    .add_plugins( settings::Schedule )    //設定画面
+    .add_plugins( sound::Schedule )       //効果音とBGM
     .add_plugins( play_game::Schedule )   //ゲームロジック
     ;
 
@@ -4,6 +4,23 @@ use super::*;
 mod map;
 mod player;
 mod minimap;
+mod auto_walk;
+mod fullmap;
+mod hud;
+mod stage;
+mod save;
+mod replay;
+mod ghost;
+mod monster;
+mod combat;
+mod traps;
+mod items;
+mod stats;
+mod cell_events;
+mod message;
+mod inspector;
+mod pause;
+mod game_over;
 
 mod spawn_methods;
 use spawn_methods::*;
@@ -15,13 +32,46 @@ pub struct Schedule;
 impl Plugin for Schedule
 {   fn build( &self, app: &mut App )
     {   app
-        //InitAppの実行後にGameStartへ遷移させる
-        .insert_resource( AfterInitAppTo ( MyState::GameStart ) )
+        //InitAppの実行後にTitleへ遷移させる
+        .insert_resource( AfterInitAppTo ( MyState::Title ) )
 
         //Resourceの登録
         .init_resource::<map::Map>()       //マップ情報
         .init_resource::<player::Player>() //プレイヤー情報
         .init_resource::<OrbitCamera>()    //極座標カメラ情報
+        .init_resource::<player::CameraTransition>() //カメラ切替アニメーション
+        .init_resource::<player::BumpNudge>() //壁にぶつかった時の揺れ
+        .init_resource::<auto_walk::AutoWalk>() //自動歩行の情報
+        .init_resource::<minimap::MinimapFog>() //ミニマップの霧
+        .init_resource::<minimap::MinimapOrientation>() //ミニマップの向き
+        .init_resource::<minimap::MinimapZoom>() //ミニマップの拡大率
+        .init_resource::<minimap::MarkerHandles>() //ミニマップのマーカーの形と色
+        .init_resource::<hud::PlayRecord>() //プレイ記録
+        .init_resource::<stage::Stage>()    //ステージ数と難易度
+        .init_resource::<replay::Recorder>() //記録中のリプレイ
+        .init_resource::<ghost::Ghost>()     //ベストタイムのゴースト
+        .init_resource::<monster::Monsters>() //モンスターの乱数と巡回先
+        .init_resource::<stats::PlayerStats>() //Playerの能力値と持ち物
+        .init_resource::<message::MessageQueue>() //表示待ちのメッセージ
+        .init_resource::<cell_events::LastFacing>() //最後に止まって向いた位置と向き
+        .add_event::<map::CellChanged>() //マスの状態変化の通知
+        .add_event::<replay::PlayerCommand>() //Playerへの指示
+        .add_event::<player::StepFinished>() //一マス進み終えた通知
+        .add_event::<player::Bumped>() //壁にぶつかった通知
+        .add_event::<cell_events::CellTriggered>() //マスのイベントのきっかけの通知
+        .add_event::<cell_events::RunAction>() //イベントの中身の通知
+
+        //アイテム表のAsset（事前ロードするので、ロードより前に登録する）
+        .add_asset::<items::ItemTable>()
+        .init_asset_loader::<items::ItemTableLoader>()
+
+        //ステージ毎のイベント表のAsset（同上）
+        .add_asset::<cell_events::EventTable>()
+        .init_asset_loader::<cell_events::EventTableLoader>()
+
+        //debug用：フリーカメラでマスの情報を調べる（Stateに関係なく使える）
+        .add_systems( Startup, inspector::spawn_text.run_if( misc::DEBUG ) )
+        .add_systems( Update, inspector::inspect_cell.run_if( misc::DEBUG ) )
 
         //ゲームプレイ前の処理
         .add_systems
@@ -30,49 +80,234 @@ impl Plugin for Schedule
                 //AppDefaultな3Dカメラを削除する(※1)
                 misc::despawn::<misc::AppDefault3dCamera>,
 
-                //ミニマップ用2Dカメラとスプライト
-                minimap::spawn_minimap,
+                //ミニマップ用2Dカメラとスプライト（タイトルから再開した時は作り直さない）
+                (   minimap::spawn_minimap,
+                    fullmap::spawn_fullmap, //全体マップ用2Dカメラ
+                    hud::spawn_hud, //右上のHUD
+                )
+                .run_if( not( any_with_component::<minimap::MinimapCamera>() ) ),
+                (   hud::reset_record, //プレイ記録の初期化
+                    stage::reset_stage, //ステージ１から始める
+                    stats::reset_stats,   //Playerの能力値と持ち物の初期化
+                    message::reset_queue, //表示待ちのメッセージを捨てる
+                    save::load_game     //CONTINUEならセーブデータで上書きする
+                        .run_if( resource_exists::<ContinueGame>() ),
+                    replay::start_playback //REPLAYならリプレイの種とステージで始める
+                        .run_if( resource_exists::<WatchReplay>() ),
+                    replay::start_recording, //リプレイの記録開始
+                )
+                .chain(),
 
-                misc::change_state::<MainLoop>, //無条件遷移
+                misc::change_state::<StageStart>, //無条件遷移
             )
         )
 
         //ステージの前処理
+        //（ポーズから戻った時に作り直さないよう、MainLoopとは別のStateで行う）
         .add_systems
-        (   OnEnter ( MyState::MainLoop ),
-            (   map::make_new_data,     //新しいMapデータを作る
+        (   OnEnter ( MyState::StageStart ),
+            (   (   map::make_new_data, //新しいMapデータを作る
+                    ghost::copy_map,    //ゴースト専用の迷路(復元の前に写す)
+                )
+                .chain(),
+                save::restore_map.run_if( resource_exists::<save::LoadedGame>() ), //開いた宝箱等の復元
+                stage::apply_lighting,  //難易度に合わせたライトの明るさ
+                stage::play_bgm,        //ステージのテーマのBGM
                 map::spawn_entity,      //Mapを3D表示する
+                player::init_player,    //playerの位置と向き
+                ghost::spawn_entity,    //ゴーストのspawn(Playerのスタート地点と向きから)
+                save::restore_player.run_if( resource_exists::<save::LoadedGame>() ), //位置と向きの復元
                 player::spawn_entity,   //playerと3Dカメラのspawn
+                monster::spawn_entity,  //モンスターのspawn(Playerから離して置く、CONTINUEなら復元する)
+                save::finish_loading.run_if( resource_exists::<save::LoadedGame>() ), //復元の終わり
+                auto_walk::reset_record, //踏破記録の初期化
+                minimap::reveal_around_player, //スタート地点から見通せるマスの踏査
                 minimap::setup_minimap, //ミニマップの初期表示
+                minimap::spawn_markers, //ミニマップのマーカー
+                fullmap::fit_to_map,    //全体マップの位置と縮尺
 
                 //Playerカメラ(Fpp&Tpp)がない場合(ほぼデバッグ時)、
                 //AppDefaultな3Dカメラ(があれば)をmapのstartへ向ける(※1)
                 look_at_map_start::<misc::AppDefault3dCamera>
                     .run_if( any_with_component::<misc::AppDefault3dCamera>() ),
+
+                apply_deferred, //spawnしたモンスターを保存できるようにする
+                (   save::save_game, //ステージ開始時点を保存する
+                    replay::save_recording,
+                )
+                .run_if( not( resource_exists::<replay::Playback>() ) ), //再生中は保存しない
+                misc::change_state::<MainLoop>, //無条件遷移
             )
             .chain() //実行順の固定
         )
 
+        //MainLoopを出たら自動歩行を中止する（ポーズ等から戻った時に古い経路で歩き出さない）
+        .add_systems( OnExit ( MyState::MainLoop ), auto_walk::cancel_walk )
+
+        //ステージ毎に、止まって向いた位置と向きを忘れる
+        .add_systems( OnEnter ( MyState::StageStart ), cell_events::reset_facing )
+
+        //ポーズ
+        .add_systems
+        (   OnEnter ( MyState::Pause ),
+            (   pause::spawn_overlay,
+                (   save::save_game,
+                    replay::save_recording,
+                )
+                .run_if( not( resource_exists::<replay::Playback>() ) ),
+            )
+        )
+        .add_systems( Update, pause::select_menu.run_if( in_state( MyState::Pause ) ) )
+        .add_systems( OnExit ( MyState::Pause ), misc::despawn::<menu::MenuOverlay> )
+
+        //戦闘（迷路のシステムはMainLoop限定なので止まる）
+        .add_systems( OnEnter ( MyState::Combat ), combat::spawn_overlay )
+        .add_systems( Update, combat::select_menu.run_if( in_state( MyState::Combat ) ) )
+        .add_systems( OnExit ( MyState::Combat ), misc::despawn::<menu::MenuOverlay> )
+
+        //持ち物画面（迷路のシステムはMainLoop限定なので止まる）
+        .add_systems( OnEnter ( MyState::Inventory ), stats::spawn_overlay )
+        .add_systems( Update, stats::select_menu.run_if( in_state( MyState::Inventory ) ) )
+        .add_systems( OnExit ( MyState::Inventory ), misc::despawn::<menu::MenuOverlay> )
+
+        //メッセージウィンドウ（迷路のシステムはMainLoop限定なので止まる）
+        .add_systems( OnEnter ( MyState::Message ), message::spawn_window )
+        .add_systems
+        (   Update,
+            (   message::select_choice, //選択肢を選ぶ
+                message::turn_page,     //[Enter]キーで早送り・ページ送り
+                message::type_text,     //本文を一文字ずつ表示する
+            )
+            .chain()
+            .run_if( in_state( MyState::Message ) )
+        )
+        .add_systems( OnExit ( MyState::Message ), misc::despawn::<message::MessageWindow> )
+
+        //ゲームオーバー
+        .add_systems
+        (   OnEnter ( MyState::GameOver ),
+            (   game_over::spawn_overlay,
+                sound::stop_bgm,
+                (   save::delete_save_data,
+                    replay::save_recording,
+                )
+                .run_if( not( resource_exists::<replay::Playback>() ) ),
+                replay::stop_playback, //再生の途中でやめた時
+            )
+        )
+        .add_systems( Update, game_over::select_menu.run_if( in_state( MyState::GameOver ) ) )
+        .add_systems( OnExit ( MyState::GameOver ), misc::despawn::<menu::MenuOverlay> )
+
+        //アプリの終了（[Esc]キーやウィンドウを閉じた時）の直前に保存する
+        //（戦闘・持ち物・メッセージの途中でも、迷路の中の状態を保存する）
+        .add_systems
+        (   Last,
+            (   save::save_game,
+                replay::save_recording,
+            )
+            .run_if( on_event::<AppExit>() )
+            .run_if
+            (   in_state( MyState::MainLoop )
+                .or_else( in_state( MyState::Pause ) )
+                .or_else( in_state( MyState::Combat ) )
+                .or_else( in_state( MyState::Inventory ) )
+                .or_else( in_state( MyState::Message ) )
+            )
+            .run_if( not( resource_exists::<replay::Playback>() ) )
+        )
+
         //メインループ
         .add_systems
         (   Update,
-            (   //テスト用：三人称視点カメラ有効化
+            (   (   pause::catch_input_keyboard, //[P]キーでポーズ
+                    stats::catch_input_keyboard  //[I]キーで持ち物画面（同時に押したらポーズを優先）
+                        .run_if( not( resource_exists::<replay::Playback>() ) ),
+                )
+                .chain(),
+
+                //三人称視点カメラ
                 (   switch_fpp_and_tpp, //[Space]キーでカメラを切り替える
-                    debug::move_orbit_camera::<player::TppCamera>, //カメラの移動
-                ),
-                // .run_if( misc::DEBUG ),
+                    player::follow_tpp_camera, //背後へ追従し、壁を避ける
+                    blend_camera_transition, //切替の途中のカメラの移動
+                )
+                .chain(),
  
                 (   //Playerを操作する
-                    (   player::catch_input_keyboard, //キー入力
-                        // player::catch_input_mouse,    //マウス
-                        // player::catch_input_gamepad,  //ゲームパッド
-                    ),
+                    (   (   auto_walk::catch_input_mouse,    //ミニマップのクリックで自動歩行
+                            auto_walk::catch_input_keyboard, //[J]キーで自動歩行、他のキーでキャンセル
+                            player::catch_input_keyboard, //キー入力
+                            // player::catch_input_mouse,    //マウス
+                            // player::catch_input_gamepad,  //ゲームパッド
+                        )
+                        .chain()
+                        .run_if( not( resource_exists::<replay::Playback>() ) ), //再生中は手動操作できない
+                        auto_walk::walk_along_route, //経路に沿って自動歩行
+                        replay::play_back,    //リプレイの再生
+                        replay::change_speed, //[[][]]キーで再生速度の変更
+                        player::apply_commands, //Playerへの指示の実行と記録
+                    )
+                    .chain(),
                     (   player::rotate_player, //playerの向きを変える
                         player::move_player,   //playerを移動する
+                        ghost::move_ghost,     //ゴーストを経過時間に合わせて動かす
+                        monster::move_monsters, //モンスターの移動アニメーション
+                        (   player::start_bump_nudge, //壁にぶつかったら揺らす
+                            player::nudge_figure,
+                        )
+                        .chain(),
+                        player::play_bump_sound, //壁にぶつかった音
+                        minimap::flash_bump,     //ぶつかった壁をミニマップで光らせる
                     ),
-                    (   minimap::turn_player, //ミニマップ上のプレイヤーの旋回
+                    (   traps::trigger_traps, //進み終えたマスの罠と仕掛けの発動
+                        monster::step_monsters, //歩数に合わせてモンスターを動かす
+                    )
+                    .chain(),
+                    (   (   cell_events::detect_enter, //進み終えたマスのイベント
+                            cell_events::detect_face,  //止まって向いたマスのイベント
+                        ),
+                        cell_events::dispatch, //定義と合うイベントの中身を知らせる
+                        (   cell_events::show_message, //イベントの中身の実行
+                            cell_events::give_rewards,
+                            cell_events::open_door,
+                            cell_events::change_stage,
+                        ),
+                        message::open_window, //メッセージがあれば表示する
+                    )
+                    .chain(),
+                    combat::check_encounter, //モンスターと隣り合ったら戦闘
+                    stage::check_goal, //ゴールに着いたら次のステージへ
+                    (   (   minimap::toggle_orientation, //[R]キーでミニマップの向きの切替
+                            minimap::turn_player,   //ミニマップ上のプレイヤーの旋回
+                            minimap::rotate_camera, //ヘディングアップ時のミニマップ用2Dカメラの回転
+                        )
+                        .chain(),
                         minimap::move_camera, //ミニマップ用2Dカメラの位置更新
+                        (   minimap::reveal_around_player, //見通せるマスを踏査済みにする
+                            map::send_changed_cells, //状態が変わったマスの通知
+                            (   minimap::repaint_changed_cells, //ミニマップのテクスチャの部分更新
+                                minimap::sync_markers,   //ミニマップのマーカーの更新
+                                map::update_objects,     //開けられた宝箱など、済んだイベントの物の差し替え
+                                map::update_doors,       //開けられた扉の取り除き
+                            ),
+                        )
+                        .chain(),
+                        (   minimap::toggle_fog, //[F1]キーで霧の切替
+                            minimap::update_marker_visibility, //霧に合わせてマーカーを表示
+                        )
+                        .chain()
+                        .run_if( misc::DEBUG ),
+                        minimap::zoom_camera, //[+][-]キーでミニマップの拡大縮小
+                        fullmap::toggle_fullmap, //[Tab]キーで全体マップの表示切替
+                        minimap::hide_in_dark_zone, //暗闇ではミニマップを隠す
+                        minimap::drop_breadcrumb,  //ミニマップに足跡を残す
+                        minimap::fade_breadcrumbs, //足跡を徐々に消す
+                        minimap::fade_bump_flashes, //壁にぶつかった印を消す
+                        auto_walk::record_footsteps, //踏破したマスの記録
                     ),
+                    (   hud::tick_elapsed, //経過時間
+                        hud::update_hud,   //HUDの表示更新
+                    )
+                    .chain(),
                 )
                 .chain()
             )
@@ -107,19 +342,73 @@ fn look_at_map_start<T: Component>
 
 ////////////////////////////////////////////////////////////////////////////////
 
-//テスト用：一人称視点カメラ⇔三人称視点カメラ切替
+//一人称視点カメラ⇔三人称視点カメラ切替（切替アニメーションを開始する）
+#[allow(clippy::type_complexity)]
 fn switch_fpp_and_tpp
-(   mut que_cameras: Query<&mut Camera, Or<( &player::FppCamera, &player::TppCamera )>>, 
+(   mut qry_fpp: Query<( &mut Camera, &Transform ), ( With<player::FppCamera>, Without<player::TppCamera> )>,
+    mut qry_tpp: Query<( &mut Camera, &Transform ), With<player::TppCamera>>,
+    qry_figure: Query<&Transform, With<player::FigureHead>>,
     mut orbit_camera: ResMut<OrbitCamera>,
+    mut transition: ResMut<player::CameraTransition>,
     inkey: Res<Input<KeyCode>>,
 )
 {   if ! inkey.just_pressed( KeyCode::Space ) { return } //[Space]キー
+    if transition.is_running() { return } //切替中は受け付けない
+
+    let Ok ( ( mut fpp_camera, fpp_transform ) ) = qry_fpp.get_single_mut() else { return };
+    let Ok ( ( mut tpp_camera, tpp_transform ) ) = qry_tpp.get_single_mut() else { return };
+    let Ok ( figure ) = qry_figure.get_single() else { return };
+
+    //切替開始時のカメラの姿勢（一人称視点カメラはFigureHeadの子なので親の回転を掛ける）
+    let to_tpp = fpp_camera.is_active;
+    let from = if to_tpp { figure.mul_transform( *fpp_transform ) } else { *tpp_transform };
+
+    //切替の途中は三人称視点カメラで描画する
+    fpp_camera.is_active = false;
+    tpp_camera.is_active = true;
+    orbit_camera.is_active = true; //極座標カメラ(Tpp)の追従は止めない
+
+    transition.start( from, to_tpp );
+}
 
-    //FppとTppのカメラの状態を反転させる
-    que_cameras.for_each_mut( | mut camera | camera.is_active = ! camera.is_active );
+//切替の途中、三人称視点カメラを開始時の姿勢から目的の姿勢へ補間する
+#[allow(clippy::type_complexity)]
+fn blend_camera_transition
+(   mut qry_fpp: Query<( &mut Camera, &Transform ), ( With<player::FppCamera>, Without<player::TppCamera> )>,
+    mut qry_tpp: Query<( &mut Camera, &mut Transform ), With<player::TppCamera>>,
+    qry_figure: Query<&Transform, ( With<player::FigureHead>, Without<player::TppCamera> )>,
+    mut orbit_camera: ResMut<OrbitCamera>,
+    mut transition: ResMut<player::CameraTransition>,
+    time: Res<Time>,
+)
+{   let transition = &mut *transition;
+    let Some ( timer ) = transition.timer.as_mut() else { return };
+
+    let Ok ( ( mut fpp_camera, fpp_transform ) ) = qry_fpp.get_single_mut() else { return };
+    let Ok ( ( mut tpp_camera, mut tpp_transform ) ) = qry_tpp.get_single_mut() else { return };
+    let Ok ( figure ) = qry_figure.get_single() else { return };
+
+    //目的の姿勢（Tppはfollow_tpp_camera()が更新した姿勢、Fppは現在の一人称視点の姿勢）
+    let to = if transition.to_tpp { *tpp_transform } else { figure.mul_transform( *fpp_transform ) };
+
+    //切替が終わったらカメラを入れ替える
+    if timer.tick( time.delta() ).finished()
+    {   if ! transition.to_tpp
+        {   fpp_camera.is_active = true;
+            tpp_camera.is_active = false;
+            orbit_camera.is_active = false;
+        }
+        transition.timer = None;
+        return;
+    }
 
-    //極座標カメラ(Tpp)用のResourceを書き換える
-    orbit_camera.is_active = ! orbit_camera.is_active; //反転
+    //ゆっくり動き出して、ゆっくり止まる
+    let t = timer.percent();
+    let t = t * t * ( 3.0 - 2.0 * t );
+    let from = transition.from;
+    let translation = from.translation.lerp( to.translation, t );
+    let rotation = from.rotation.slerp( to.rotation, t );
+    *tpp_transform = Transform { translation, rotation, ..default() };
 }
 
 ////////////////////////////////////////////////////////////////////////////////
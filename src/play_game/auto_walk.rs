@@ -0,0 +1,170 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//自動歩行のResource
+#[derive( Resource, Default )]
+pub struct AutoWalk
+{   route    : VecDeque<IVec2>, //目的地までの経路（先頭が次のマス）
+    visited  : HashSet<IVec2>,  //踏破済みのマス
+    junctions: Vec<IVec2>,      //通過した分岐点（末尾が最新）
+}
+
+impl AutoWalk
+{   //自動歩行を中止する
+    pub fn cancel( &mut self )
+    {   self.route.clear();
+    }
+
+    //未踏の方角が残っている最新の分岐点を返す（現在地は除く）
+    fn last_unexplored_junction( &self, map: &map::Map, here: IVec2 ) -> Option<IVec2>
+    {   self.junctions.iter().rev().copied().find
+        (   | &junction |
+            junction != here &&
+            map.get_sides_space( junction ).iter()
+            .any( | &news | ! self.visited.contains( &( junction + news ) ) )
+        )
+    }
+}
+
+//自動歩行を開始するキー
+const KEY_GOTO_JUNCTION: KeyCode = KeyCode::J;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ステージ開始時に踏破記録を初期化する
+pub fn reset_record
+(   mut auto_walk: ResMut<AutoWalk>,
+    player: Res<player::Player>,
+)
+{   *auto_walk = AutoWalk::default();
+    auto_walk.visited.insert( player.position );
+}
+
+//MainLoopを出る時に自動歩行を中止する（戻った時に古い経路で歩き出さない）
+pub fn cancel_walk( mut auto_walk: ResMut<AutoWalk> )
+{   auto_walk.cancel();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ミニマップのマスをクリックしたら、そこまでの経路をセットする
+pub fn catch_input_mouse
+(   qry_window: Query<&Window>,
+    qry_camera: Query<( &Camera, &GlobalTransform ), With<minimap::MinimapCamera>>,
+    mut auto_walk: ResMut<AutoWalk>,
+    player: Res<player::Player>,
+    map: Res<map::Map>,
+    fog: Res<minimap::MinimapFog>,
+    mouse_button: Res<Input<MouseButton>>,
+)
+{   if ! mouse_button.just_pressed( MouseButton::Left ) { return }
+
+    let Ok ( window ) = qry_window.get_single() else { return };
+    let Ok ( ( camera, transform ) ) = qry_camera.get_single() else { return };
+    let Some ( cursor ) = window.cursor_position() else { return };
+    let Some ( rect ) = camera.logical_viewport_rect() else { return };
+
+    //クリックした位置がミニマップの外なら
+    if ! rect.contains( cursor ) { return }
+
+    //ミニマップをクリックしたら、それまでの経路は捨てる
+    auto_walk.cancel();
+
+    //クリックしたマス目を求める(viewport内の相対位置を渡す)
+    let Some ( world ) = camera.viewport_to_world_2d( transform, cursor - rect.min ) else { return };
+    let goal = minimap::world_to_cell( world );
+    if ! fog.is_disabled && ! map.is_explored( goal ) { return } //霧の中は選べない
+
+    //経路が見つかれば自動歩行を開始する
+    let Some ( route ) = map.find_path( player.position, goal ) else { return };
+    auto_walk.route = route.into();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//キー入力で自動歩行を開始・キャンセルする
+pub fn catch_input_keyboard
+(   mut auto_walk: ResMut<AutoWalk>,
+    player: Res<player::Player>,
+    map: Res<map::Map>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   //[J]キー以外のキーは手動操作とみなし、自動歩行をキャンセルする
+    if inkey.get_just_pressed().any( | &key | key != KEY_GOTO_JUNCTION )
+    {   auto_walk.cancel();
+        return;
+    }
+
+    //[J]キーで未踏の方角が残っている最新の分岐点へ向かう
+    if ! inkey.just_pressed( KEY_GOTO_JUNCTION ) { return }
+
+    let Some ( junction ) = auto_walk.last_unexplored_junction( &map, player.position ) else { return };
+    let Some ( route ) = map.find_path( player.position, junction ) else { return };
+    auto_walk.route = route.into();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//経路に沿って、旋回と前進を一つずつ指示する
+pub fn walk_along_route
+(   mut auto_walk: ResMut<AutoWalk>,
+    mut evt_command: EventWriter<replay::PlayerCommand>,
+    player: Res<player::Player>,
+    map: Res<map::Map>,
+)
+{   //Playerが停止していないなら、行動の完了を待つ
+    if ! player.is_stop() { return }
+
+    //到着済みのマスを経路から取り除く
+    while auto_walk.route.front() == Some ( &player.position )
+    {   auto_walk.route.pop_front();
+    }
+    let Some ( &next ) = auto_walk.route.front() else { return };
+
+    //次のマスの方角を求める（隣接していないなら経路が壊れているので中止）
+    let Some ( news ) = NEWS.into_iter().find( | &news | player.position + news == next )
+    else { auto_walk.cancel(); return };
+
+    //向きが合っていれば前進、そうでなければ旋回する（真後ろは右旋回２回）
+    let action = if news == player.direction
+    {   player::InAction::Forward
+    }
+    else if news == player.direction.turn_left()
+    {   player::InAction::TurnLeft
+    }
+    else
+    {   player::InAction::TurnRight
+    };
+
+    //進入禁止のマスへは進めないので中止
+    if action == player::InAction::Forward && map.is_noentry( next )
+    {   auto_walk.cancel();
+        return;
+    }
+
+    evt_command.send( replay::PlayerCommand::Action ( action ) );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//踏破したマスと分岐点を記録する
+pub fn record_footsteps
+(   mut auto_walk: ResMut<AutoWalk>,
+    player: Res<player::Player>,
+    map: Res<map::Map>,
+)
+{   if ! player.is_changed() { return }
+
+    let cell = player.position;
+    auto_walk.visited.insert( cell );
+
+    //分岐点（三方以上が空地）なら最新として記録し直す
+    if map.get_sides_space( cell ).len() < 3 { return }
+    auto_walk.junctions.retain( | &junction | junction != cell );
+    auto_walk.junctions.push( cell );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -0,0 +1,279 @@
+use super::*;
+use bevy::
+{   asset::{ AssetLoader, LoadContext, LoadedAsset },
+    reflect::{ TypePath, TypeUuid },
+    utils::BoxedFuture,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+//イベントを置く場所の種類（迷路はランダムなので、作った後で条件に合う空いたマスを選ぶ）
+#[derive( Clone, Copy, PartialEq, Eq, Deserialize, Debug )]
+pub enum Place
+{   Deadend,    //袋小路
+    Corridor,   //通路
+    Junction,   //分岐点
+    BeforeGoal, //ゴールの一つ手前
+}
+
+//イベントが起きるきっかけ
+#[derive( Clone, Copy, PartialEq, Eq, Deserialize, Debug )]
+pub enum Trigger
+{   Enter,    //マスに入った
+    Face,     //マスの方を向いて止まった
+    Interact, //マスの方を向いて[Enter]キーを押した
+}
+
+//イベントのマスに置く物（3Dで表示し、進入禁止になる。一度だけのイベントが済むと姿が変わる）
+#[derive( Clone, Copy, PartialEq, Eq, Deserialize, Debug )]
+pub enum Object
+{   Chest, //宝箱（済んだら開いた宝箱になる）
+}
+
+//イベントの中身（並んだ順に実行する）
+#[derive( Clone, PartialEq, Eq, Deserialize, Debug )]
+pub enum EventAction
+{   Message ( Vec<String> ), //メッセージを表示する
+    Ask { lines: Vec<String>, choices: Vec<Choice> }, //選択肢を出し、選ばれた中身を実行する
+    GiveItem ( String ),     //アイテムを渡す
+    GiveGold ( u32 ),        //お金を渡す
+    OpenDoor ( usize ),      //番号の鍵のかかった扉を開ける（スタートに近い順に0から）
+    ChangeStage ( i32 ),     //ステージを進める（負なら戻る）
+}
+
+//選択肢（はい/いいえ や 番号付きの項目）
+#[derive( Clone, PartialEq, Eq, Deserialize, Debug )]
+pub struct Choice
+{   pub label  : String,           //表示文字列
+    pub actions: Vec<EventAction>, //選ばれた時の中身
+}
+
+//イベントの定義
+#[derive( Clone, Deserialize, Debug )]
+pub struct EventDef
+{   pub id     : String,           //識別子（debug用）
+    pub place  : Place,            //置く場所の種類
+    pub trigger: Trigger,          //起きるきっかけ
+    #[serde( default )]
+    pub once   : bool,             //一度だけ起きるならtrue
+    #[serde( default )]
+    pub object : Option<Object>,   //マスに置く物（宝箱など）
+    pub actions: Vec<EventAction>, //中身
+}
+
+impl EventDef
+{   //扉の鍵を入れた宝箱（扉を置く時に、迷路の生成が鍵の取れる場所に置く）
+    pub fn key_chest() -> Self
+    {   Self
+        {   id     : "key_chest".to_string(),
+            place  : Place::Deadend,
+            trigger: Trigger::Interact,
+            once   : true,
+            object : Some ( Object::Chest ),
+            actions: vec!
+            [   EventAction::Message ( TEXT_KEY_CHEST.iter().map( ToString::to_string ).collect() ),
+                EventAction::GiveItem ( items::ITEM_ID_KEY.to_string() ),
+            ],
+        }
+    }
+}
+
+//鍵の宝箱を開けた時のメッセージ
+const TEXT_KEY_CHEST: [ &str; 2 ] = [ "YOU OPENED THE CHEST.", "FOUND KEY." ];
+
+//ステージ毎のイベント表のAsset（assets/data/*.events.ron）
+#[derive( Deserialize, TypeUuid, TypePath, Debug )]
+#[uuid = "2fd41603-17f6-4be5-b839-332024ed9dcc"]
+pub struct EventTable
+{   pub events: Vec<EventDef>, //表の順に置く（置けなければ飛ばす）
+}
+
+//イベント表のAssetLoader（渡すアイテムのidはアイテム表と照らし合わせる）
+#[derive( Default )]
+pub struct EventTableLoader;
+
+//中身の中で、アイテム表にないidのアイテムを渡すものを探す（選択肢の中身も調べる）
+fn find_unknown_item<'a>( actions: &'a [ EventAction ], items: &items::ItemTable ) -> Option<&'a str>
+{   actions.iter().find_map
+    (   | action | match action
+        {   EventAction::GiveItem ( id ) => items.get( id ).is_none().then_some( id.as_str() ),
+            EventAction::Ask { choices, .. } =>
+                choices.iter().find_map( | choice | find_unknown_item( &choice.actions, items ) ),
+            _ => None,
+        }
+    )
+}
+
+impl AssetLoader for EventTableLoader
+{   fn load<'a>
+    (   &'a self,
+        bytes: &'a [ u8 ],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>>
+    {   Box::pin
+        (   async move
+            {   let table: EventTable = ron::de::from_bytes( bytes )?;
+
+                //知らないアイテムを渡すイベントがあればロードを失敗させる
+                let items: items::ItemTable = ron::de::from_bytes( &load_context.read_asset_bytes( ASSETS_DATA_ITEMS ).await? )?;
+                for event in &table.events
+                {   if let Some ( id ) = find_unknown_item( &event.actions, &items )
+                    {   let msg = format!( "Event \"{}\" gives an unknown item \"{id}\"", event.id );
+                        return Err ( bevy::asset::Error::msg( msg ) );
+                    }
+
+                    //物を置くイベントは、済んだら姿が変わるので一度だけに限る
+                    if event.object.is_some() && ! event.once
+                    {   let msg = format!( "Event \"{}\" places an object but is not once", event.id );
+                        return Err ( bevy::asset::Error::msg( msg ) );
+                    }
+                }
+
+                load_context.set_default_asset( LoadedAsset::new( table ) );
+                Ok ( () )
+            }
+        )
+    }
+
+    fn extensions( &self ) -> &[ &str ]
+    {   &[ "events.ron" ]
+    }
+}
+
+//マスのイベントのきっかけが起きたことを知らせるEvent
+#[derive( Event )]
+pub struct CellTriggered
+{   pub cell   : IVec2,   //イベントのマス
+    pub trigger: Trigger, //きっかけ
+}
+
+//イベントの中身を一つずつ知らせるEvent（中身の種類毎のhandlerが購読する）
+#[derive( Event )]
+pub struct RunAction ( pub EventAction );
+
+//最後に止まって向いた位置と向きのResource（Faceのイベントを一度だけ起こす）
+#[derive( Resource, Default )]
+pub struct LastFacing ( Option<( IVec2, News )> );
+
+////////////////////////////////////////////////////////////////////////////////
+
+//事前ロード済みの、ステージのイベント表を取り出す
+pub fn table<'a>
+(   asset_svr: &Res<AssetServer>,
+    tables: &'a Res<Assets<EventTable>>,
+    stage: &stage::Stage,
+) -> Option<&'a EventTable>
+{   tables.get( &asset_svr.load( stage.difficulty().events ) )
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//進み終えたマスのイベントを起こす
+pub fn detect_enter
+(   mut evt_step: EventReader<player::StepFinished>,
+    mut evt_triggered: EventWriter<CellTriggered>,
+)
+{   for &player::StepFinished ( cell ) in evt_step.iter()
+    {   evt_triggered.send( CellTriggered { cell, trigger: Trigger::Enter } );
+    }
+}
+
+//ステージ開始時に、最後に向いた位置と向きを忘れる（同じ位置と向きから始まっても起きるように）
+pub fn reset_facing( mut last: ResMut<LastFacing> )
+{   last.0 = None;
+}
+
+//止まって向いたマスのイベントを起こす（同じ位置と向きでは一度だけ）
+pub fn detect_face
+(   mut evt_triggered: EventWriter<CellTriggered>,
+    player: Res<player::Player>,
+    mut last: ResMut<LastFacing>,
+)
+{   if ! player.is_stop() { return }
+
+    let here = Some ( ( player.position, player.direction ) );
+    if last.0 == here { return }
+    last.0 = here;
+
+    let cell = player.position + player.direction;
+    evt_triggered.send( CellTriggered { cell, trigger: Trigger::Face } );
+}
+
+//マスのイベントのきっかけが定義と合えば、中身を順に知らせる（宝箱なら開ける音を鳴らす）
+pub fn dispatch
+(   mut evt_triggered: EventReader<CellTriggered>,
+    mut evt_action: EventWriter<RunAction>,
+    mut evt_sfx: EventWriter<sound::PlaySfx>,
+    mut map: ResMut<map::Map>,
+)
+{   for &CellTriggered { cell, trigger } in evt_triggered.iter()
+    {   let Some ( event ) = map.cell_event( cell ) else { continue };
+        if event.trigger != trigger { continue }
+
+        let event = event.clone();
+        if event.once { map.finish_event( cell ) }
+        if event.object == Some ( Object::Chest ) { evt_sfx.send( sound::PlaySfx ( sound::Sfx::ChestOpen ) ) }
+        evt_action.send_batch( event.actions.into_iter().map( RunAction ) );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//メッセージと選択肢を表示待ちにする
+pub fn show_message
+(   mut evt_action: EventReader<RunAction>,
+    mut queue: ResMut<message::MessageQueue>,
+)
+{   for RunAction ( action ) in evt_action.iter()
+    {   match action
+        {   EventAction::Message ( lines ) => queue.push( lines ),
+            EventAction::Ask { lines, choices } => queue.ask( lines, choices ),
+            _ => (),
+        }
+    }
+}
+
+//アイテムとお金を渡す
+pub fn give_rewards
+(   mut evt_action: EventReader<RunAction>,
+    mut stats: ResMut<stats::PlayerStats>,
+)
+{   for RunAction ( action ) in evt_action.iter()
+    {   match action
+        {   EventAction::GiveItem ( id ) => stats.add_item( id ),
+            EventAction::GiveGold ( gold ) => stats.gold += gold,
+            _ => (),
+        }
+    }
+}
+
+//番号の鍵のかかった扉を開ける（他の扉は閉じたまま）
+pub fn open_door
+(   mut evt_action: EventReader<RunAction>,
+    mut map: ResMut<map::Map>,
+)
+{   for RunAction ( action ) in evt_action.iter()
+    {   let &EventAction::OpenDoor ( number ) = action else { continue };
+        map.open_numbered_door( number ); //開いている扉や無い番号なら何もしない
+    }
+}
+
+//ステージを移る（ゴールではないので記録は残さない）
+pub fn change_stage
+(   mut evt_action: EventReader<RunAction>,
+    mut stage: ResMut<stage::Stage>,
+    mut record: ResMut<hud::PlayRecord>,
+    mut next_state: ResMut<NextState<MyState>>,
+)
+{   for RunAction ( action ) in evt_action.iter()
+    {   let EventAction::ChangeStage ( offset ) = action else { continue };
+
+        record.reset_stage();
+        stage.number = ( stage.number + offset ).max( 1 );
+        next_state.set( MyState::StageStart );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -0,0 +1,326 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//戦闘の能力値
+#[derive( Clone, Copy, Serialize, Deserialize, Debug )]
+pub struct Status
+{   pub hp     : i32, //体力
+    pub max_hp : i32, //体力の最大値
+    pub attack : i32, //攻撃力
+    pub defense: i32, //防御力
+}
+
+//戦闘の結果
+#[derive( Clone, Copy, PartialEq, Eq )]
+pub enum Outcome
+{   Won,  //モンスターを倒した
+    Lost, //Playerが倒れた
+    Fled, //逃げた
+}
+
+//戦闘中のResource（相手のモンスターと戦闘の記録）
+#[derive( Resource )]
+pub struct Battle
+{   monster         : Entity,           //相手のモンスター
+    log             : VecDeque<String>, //戦闘の記録（新しいものが末尾）
+    outcome         : Option<Outcome>,  //決着したらSome
+    is_choosing_item: bool,             //道具を選んでいる間true
+    rng             : StdRng,           //戦闘の乱数（迷路の種と歩数から作る）
+}
+
+//戦闘の設定
+const DAMAGE_SPREAD     : i32 = 1;   //ダメージのばらつき(±)
+const FLEE_ODDS         : f64 = 0.6; //逃げられる確率
+const MONSTER_REST_STEPS: u32 = 6;   //逃げた後、モンスターが追ってこない歩数
+const COMBAT_SEED_SALT  : u64 = 0x434F_4D42_4154_5321; //迷路の種から戦闘の乱数列を作る
+const BATTLE_LOG_LINES  : usize = 3; //表示する戦闘の記録の行数
+const BATTLE_TEXT_WIDTH : usize = 28; //本文の一行の文字数（中央揃えで桁をそろえる）
+
+counted_array!
+(   const TEXT_COMBAT: [ init_app::MessageSect; _ ] =
+    [   ( "ENCOUNTER!", ASSETS_FONT_ORBITRON_BLACK, PIXELS_PER_GRID * 2.0, Color::ORANGE_RED ),
+    ]
+);
+
+counted_array!
+(   const COMBAT_MENU: [ menu::MenuItem; _ ] =
+    [   menu::MenuItem::Attack,
+        menu::MenuItem::Defend,
+        menu::MenuItem::Item,
+        menu::MenuItem::Flee,
+    ]
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+//モンスターと隣り合ったら（同じマスに入ったら）戦闘を始める
+//（戦闘の乱数は迷路の種と歩数で決まるので、リプレイでも同じ結果になる）
+pub fn check_encounter
+(   qry_monster: Query<( Entity, &monster::Monster )>,
+    mut next_state: ResMut<NextState<MyState>>,
+    mut cmds: Commands,
+    player: Res<player::Player>,
+    stage: Res<stage::Stage>,
+    record: Res<hud::PlayRecord>,
+)
+{   //移動の途中なら、マスに入り切るまで待つ（他の遷移が決まっていれば待つ）
+    if ! player.is_stop() || next_state.0.is_some() { return }
+
+    //モンスターはマス単位で判定する（移動アニメーションの途中でも位置は決まっている）
+    let Some ( ( monster, _ ) ) = qry_monster.iter().find
+    (   | ( _, monster ) |
+        {   let distance = ( monster.position - player.position ).abs();
+            monster.rest == 0 && distance.x + distance.y <= 1
+        }
+    )
+    else { return };
+
+    let log = VecDeque::from( [ "A MONSTER APPEARS!".to_string() ] );
+    let rng = StdRng::seed_from_u64( stage.map_seed() ^ COMBAT_SEED_SALT ^ record.stage_steps as u64 );
+    cmds.insert_resource( Battle { monster, log, outcome: None, is_choosing_item: false, rng } );
+    next_state.set( MyState::Combat );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//戦闘画面の本文（双方の体力と戦闘の記録）
+fn battle_lines( player: &Status, monster: &Status, battle: &Battle ) -> Vec<String>
+{   let width = BATTLE_TEXT_WIDTH;
+    let mut lines = vec!
+    [   format!( "{:<width$}", format!( "MONSTER HP {:>3}/{:<3}", monster.hp.max( 0 ), monster.max_hp ) ),
+        format!( "{:<width$}", format!( "YOU     HP {:>3}/{:<3}", player.hp.max( 0 ), player.max_hp ) ),
+        String::new(),
+    ];
+
+    //記録は新しい方から決まった行数だけ表示する（足りなければ空行）
+    let skip = battle.log.len().saturating_sub( BATTLE_LOG_LINES );
+    let log: Vec<_> = battle.log.iter().skip( skip ).collect();
+    for i in 0..BATTLE_LOG_LINES
+    {   let line = log.get( i ).map_or( "", | line | line.as_str() );
+        lines.push( format!( "{line:<width$}" ) );
+    }
+
+    lines
+}
+
+//戦闘画面をspawnする（決着したら[OK]だけ、道具を選ぶ間は持ち物のメニューにする）
+fn spawn_battle_overlay
+(   cmds: &mut Commands,
+    asset_svr: &Res<AssetServer>,
+    stats: &stats::PlayerStats,
+    table: &items::ItemTable,
+    monster: &Status,
+    battle: &Battle,
+)
+{   let body = battle_lines( &stats.status, monster, battle );
+    let menu = if battle.outcome.is_some()
+    {   menu::Menu::new( &[ menu::MenuItem::Ok ], &[] )
+    }
+    else if battle.is_choosing_item
+    {   stats.item_menu( table, true )
+    }
+    else
+    {   let disabled: &[ _ ] = if stats.inventory.is_empty() { &[ menu::MenuItem::Item ] } else { &[] };
+        menu::Menu::new( &COMBAT_MENU, disabled )
+    };
+    menu::spawn_overlay( cmds, asset_svr, &TEXT_COMBAT, &body, menu );
+}
+
+//戦闘画面をspawnする
+pub fn spawn_overlay
+(   qry_monster: Query<&monster::Monster>,
+    battle: Res<Battle>,
+    stats: Res<stats::PlayerStats>,
+    mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+    tables: Res<Assets<items::ItemTable>>,
+)
+{   let Ok ( monster ) = qry_monster.get( battle.monster ) else { return };
+    let Some ( table ) = items::table( &asset_svr, &tables ) else { return };
+    spawn_battle_overlay( &mut cmds, &asset_svr, &stats, table, &monster.status, &battle );
+}
+
+//ダメージを計算する（最低でも1）
+fn damage( attack: i32, defense: i32, rng: &mut StdRng ) -> i32
+{   ( attack - defense + rng.gen_range( -DAMAGE_SPREAD..=DAMAGE_SPREAD ) ).max( 1 )
+}
+
+//モンスターを倒したら経験値とお金を得る
+fn defeat_monster( battle: &mut Battle, stats: &mut stats::PlayerStats, stage: &stage::Stage )
+{   let ( exp, gold ) = stage.difficulty().reward;
+    battle.log.push_back( "THE MONSTER IS DEFEATED.".to_string() );
+    battle.log.push_back( format!( "GOT {exp} EXP AND {gold} GOLD." ) );
+    battle.outcome = Some ( Outcome::Won );
+
+    stats.gold += gold;
+    if stats.gain_exp( exp ) > 0
+    {   battle.log.push_back( format!( "LEVEL UP! NOW LV {}.", stats.level ) );
+    }
+}
+
+//決定された項目で一手ずつ戦う（Playerの後にモンスターが行動する）
+//（選んだ項目はリプレイに記録し、再生中は記録から選ぶ）
+#[allow(clippy::too_many_arguments)]
+pub fn select_menu
+(   mut evt_selected: EventReader<menu::MenuSelected>,
+    mut qry_monster: Query<&mut monster::Monster>,
+    qry_marker: Query<( Entity, &monster::MonsterMarker )>,
+    qry_overlay: Query<Entity, With<menu::MenuOverlay>>,
+    mut qry_body: Query<&mut Text, With<menu::MenuBody>>,
+    opt_battle: Option<ResMut<Battle>>,
+    mut stats: ResMut<stats::PlayerStats>,
+    mut next_state: ResMut<NextState<MyState>>,
+    mut cmds: Commands,
+    mut recorder: ResMut<replay::Recorder>,
+    mut opt_playback: Option<ResMut<replay::Playback>>,
+    stage: Res<stage::Stage>,
+    record: Res<hud::PlayRecord>,
+    time: Res<Time>,
+    asset_svr: Res<AssetServer>,
+    tables: Res<Assets<items::ItemTable>>,
+)
+{   let Some ( mut battle ) = opt_battle else { return };
+    let Ok ( mut monster ) = qry_monster.get_mut( battle.monster ) else { return };
+    let Some ( table ) = items::table( &asset_svr, &tables ) else { return };
+    let playback = opt_playback.as_deref_mut();
+    let Some ( item ) = replay::select_item( &mut evt_selected, playback, stage.number, &time ) else { return };
+    recorder.record( stage.number, record.stage_elapsed, replay::PlayerCommand::Select ( item ) );
+
+    //決着後の[OK]で迷路へ戻る
+    if let Some ( outcome ) = battle.outcome
+    {   match outcome
+        {   Outcome::Won =>
+            {   cmds.entity( battle.monster ).despawn_recursive();
+                qry_marker.iter()
+                .filter( | ( _, marker ) | marker.0 == battle.monster )
+                .for_each( | ( id, _ ) | cmds.entity( id ).despawn_recursive() );
+                next_state.set( MyState::MainLoop );
+            }
+            Outcome::Fled =>
+            {   monster.rest = MONSTER_REST_STEPS;
+                next_state.set( MyState::MainLoop );
+            }
+            Outcome::Lost => next_state.set( MyState::GameOver ),
+        }
+        cmds.remove_resource::<Battle>();
+        return;
+    }
+
+    //Playerの行動
+    let battle = &mut *battle;
+    let stats = &mut *stats;
+    let mut battle_rng = battle.rng.clone(); //battleを書き換えながら使うので取り出す（最後に戻す）
+    let rng = &mut battle_rng;
+    let mut is_defending = false;
+    let mut is_menu_changed = false;
+    match item
+    {   menu::MenuItem::Attack =>
+        {   let damage = damage( stats.status.attack, monster.status.defense, rng );
+            monster.status.hp -= damage;
+            battle.log.push_back( format!( "YOU HIT FOR {damage}." ) );
+            if monster.status.hp <= 0 { defeat_monster( battle, stats, &stage ) }
+        }
+        menu::MenuItem::Defend =>
+        {   is_defending = true;
+            battle.log.push_back( "YOU DEFEND.".to_string() );
+        }
+        menu::MenuItem::Item =>
+        {   //持ち物のメニューに切り替える（まだ手番は使わない）
+            battle.is_choosing_item = true;
+            qry_overlay.for_each( | id | cmds.entity( id ).despawn_recursive() );
+            spawn_battle_overlay( &mut cmds, &asset_svr, stats, table, &monster.status, battle );
+            return;
+        }
+        menu::MenuItem::Use ( index ) =>
+        {   let Some ( used ) = stats.use_item( index, table, true ) else { return };
+            battle.is_choosing_item = false;
+            is_menu_changed = true;
+
+            battle.log.push_back( format!( "YOU USE {}.", used.name ) );
+            if let items::ItemEffect::Damage ( damage ) = used.effect
+            {   monster.status.hp -= damage;
+                battle.log.push_back( format!( "IT HITS FOR {damage}." ) );
+                if monster.status.hp <= 0 { defeat_monster( battle, stats, &stage ) }
+            }
+        }
+        menu::MenuItem::Back =>
+        {   battle.is_choosing_item = false;
+            qry_overlay.for_each( | id | cmds.entity( id ).despawn_recursive() );
+            spawn_battle_overlay( &mut cmds, &asset_svr, stats, table, &monster.status, battle );
+            return;
+        }
+        menu::MenuItem::Flee =>
+        {   if rng.gen_bool( FLEE_ODDS )
+            {   battle.log.push_back( "YOU GOT AWAY.".to_string() );
+                battle.outcome = Some ( Outcome::Fled );
+            }
+            else
+            {   battle.log.push_back( "YOU FAILED TO FLEE.".to_string() );
+            }
+        }
+        _ => return,
+    }
+
+    //モンスターの行動（防御中は防御力が倍）
+    if battle.outcome.is_none()
+    {   let player = &mut stats.status;
+        let defense = player.defense * if is_defending { 2 } else { 1 };
+        let damage = damage( monster.status.attack, defense, rng );
+        player.hp -= damage;
+        battle.log.push_back( format!( "THE MONSTER HITS FOR {damage}." ) );
+        if player.hp <= 0
+        {   player.hp = 0;
+            battle.log.push_back( "YOU ARE DEFEATED.".to_string() );
+            battle.outcome = Some ( Outcome::Lost );
+        }
+    }
+
+    battle.rng = battle_rng;
+
+    if battle.outcome.is_some() || is_menu_changed
+    {   //決着したら[OK]だけのメニューで、道具を使ったら元のメニューで作り直す
+        qry_overlay.for_each( | id | cmds.entity( id ).despawn_recursive() );
+        spawn_battle_overlay( &mut cmds, &asset_svr, stats, table, &monster.status, battle );
+    }
+    else
+    {   //本文だけ書き換える
+        let Ok ( mut text ) = qry_body.get_single_mut() else { return };
+        let lines = battle_lines( &stats.status, &monster.status, battle );
+        for ( section, line ) in text.sections.iter_mut().zip( lines )
+        {   section.value = format!( "{line}\n" );
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests
+{   use super::*;
+
+    //ダメージは攻撃力－防御力の±ばらつきに収まり、最低でも1
+    #[test]
+    fn damage_spread_and_minimum()
+    {   let mut rng = StdRng::seed_from_u64( 0 );
+        for _ in 0..100
+        {   let value = damage( 10, 4, &mut rng );
+            assert!( ( 6 - DAMAGE_SPREAD..=6 + DAMAGE_SPREAD ).contains( &value ) );
+            assert_eq!( damage( 1, 99, &mut rng ), 1 );
+        }
+    }
+
+    //同じ種なら同じダメージの列になる（リプレイで同じ結果になる）
+    #[test]
+    fn damage_is_deterministic()
+    {   let mut rng1 = StdRng::seed_from_u64( 42 );
+        let mut rng2 = StdRng::seed_from_u64( 42 );
+        let list1: Vec<_> = ( 0..20 ).map( | _ | damage( 8, 3, &mut rng1 ) ).collect();
+        let list2: Vec<_> = ( 0..20 ).map( | _ | damage( 8, 3, &mut rng2 ) ).collect();
+        assert_eq!( list1, list2 );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
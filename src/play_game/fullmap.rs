@@ -0,0 +1,101 @@
+use super::*;
+use minimap::MinimapTrait;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//全体マップ用2DカメラのComponent
+#[derive( Component )]
+pub struct FullmapCamera;
+
+//全体マップの背景のComponent
+#[derive( Component )]
+pub struct FullmapBackdrop;
+
+//全体マップの表示切替キー
+const KEY_TOGGLE_FULLMAP: KeyCode = KeyCode::Tab;
+
+//全体マップ専用のレンダリングレイヤー（ミニマップ用カメラには映らない）
+const RENDER_LAYER_FULLMAP: u8 = 1;
+
+//全体マップの設定
+const COLOR_SPRITE_FULLMAP_BG: Color = Color::rgba( 0.0, 0.0, 0.0, 0.85 ); //背景
+const MARGIN_FULLMAP: f32 = 1.05; //マップの周囲の余白(倍率)
+
+////////////////////////////////////////////////////////////////////////////////
+
+//全体マップ用の2Dカメラと背景をspawnする（ミニマップのスプライトを3D viewportに重ねて映す）
+//位置と縮尺はステージ毎にfit_to_map()で合わせる
+pub fn spawn_fullmap
+(   mut cmds: Commands,
+)
+{   let viewport = Some
+    (   camera::Viewport
+        {   physical_position: SCREEN_FRAME.viewport.origin.as_uvec2(),
+            physical_size    : SCREEN_FRAME.viewport.size.as_uvec2(),
+            ..default()
+        }
+    );
+    let order = ORDER_CAMERA2D_FULLMAP;
+    let is_active = false; //[Tab]キーで表示する
+    let layers = RenderLayers::from_layers( &[ 0, RENDER_LAYER_FULLMAP ] );
+
+    cmds.spawn( ( Camera2dBundle::default(), FullmapCamera ) )
+    .insert( Camera { viewport, order, is_active, ..default() } )
+    .insert( Camera2d { clear_color: CAMERA2D_BGCOLOR } )
+    .insert( layers )
+    ;
+
+    //3D表示を隠す背景
+    let color = COLOR_SPRITE_FULLMAP_BG;
+
+    cmds.spawn( ( SpriteBundle::default(), FullmapBackdrop ) )
+    .insert( Sprite { color, ..default() } )
+    .insert( RenderLayers::layer( RENDER_LAYER_FULLMAP ) )
+    ;
+}
+
+//マップ全体がviewportに収まるよう、カメラと背景の位置と縮尺を合わせる
+#[allow(clippy::type_complexity)]
+pub fn fit_to_map
+(   mut qry_camera: Query<( &mut Transform, &mut OrthographicProjection ), With<FullmapCamera>>,
+    mut qry_backdrop: Query<( &mut Transform, &mut Sprite ), ( With<FullmapBackdrop>, Without<FullmapCamera> )>,
+    map: Res<map::Map>,
+)
+{   let Ok ( ( mut camera, mut projection ) ) = qry_camera.get_single_mut() else { return };
+    let Ok ( ( mut backdrop, mut sprite ) ) = qry_backdrop.get_single_mut() else { return };
+
+    //マップ全体がviewportに収まる縮尺
+    let map_size = map.size().as_vec2() * PIXELS_PER_GRID;
+    let ratio = map_size / SCREEN_FRAME.viewport.size;
+    let scale = ratio.x.max( ratio.y ) * MARGIN_FULLMAP;
+    projection.scale = scale;
+
+    //カメラをマップの中心に置く
+    let translation = ( map.size() / 2 ).to_minimap_center();
+    camera.translation = translation;
+
+    //背景はviewportを覆う大きさにする
+    sprite.custom_size = Some ( SCREEN_FRAME.viewport.size * scale );
+    backdrop.translation = translation.truncate().extend( DEPTH_SPRITE_FULLMAP_BG );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//全体マップの表示を切り替える(トグル動作)
+//（暗闇のマスにいる間は表示できない）
+pub fn toggle_fullmap
+(   mut qry_camera: Query<&mut Camera, With<FullmapCamera>>,
+    map: Res<map::Map>,
+    player: Res<player::Player>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   if ! inkey.just_pressed( KEY_TOGGLE_FULLMAP ) { return }
+    if map.is_darkzone( player.position ) { return }
+    let Ok ( mut camera ) = qry_camera.get_single_mut() else { return };
+
+    camera.is_active = ! camera.is_active;
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -0,0 +1,51 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ゲームオーバーの設定
+counted_array!
+(   const TEXT_GAME_OVER: [ init_app::MessageSect; _ ] =
+    [   ( "GAME OVER", ASSETS_FONT_ORBITRON_BLACK, PIXELS_PER_GRID * 2.0, Color::CRIMSON ),
+    ]
+);
+
+counted_array!
+(   const GAME_OVER_MENU: [ menu::MenuItem; _ ] =
+    [   menu::MenuItem::Retry,
+        menu::MenuItem::Title,
+    ]
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ゲームオーバー画面をspawnする
+pub fn spawn_overlay
+(   mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+    stage: Res<stage::Stage>,
+)
+{   //今回の種でのステージ毎のベストタイム（同じ種なら同じ迷路なので比べられる）
+    let mut body = vec![ format!( "BEST TIMES ON SEED {:016X}", stage.seed ), String::new() ];
+    body.extend( scores::table_lines( stage.seed ) );
+
+    let menu = menu::Menu::new( &GAME_OVER_MENU, &[] );
+    menu::spawn_overlay( &mut cmds, &asset_svr, &TEXT_GAME_OVER, &body, menu );
+}
+
+//決定された項目に応じて遷移する
+pub fn select_menu
+(   mut evt_selected: EventReader<menu::MenuSelected>,
+    mut next_state: ResMut<NextState<MyState>>,
+)
+{   for menu::MenuSelected ( item ) in evt_selected.iter()
+    {   match item
+        {   menu::MenuItem::Retry => next_state.set( MyState::GameStart ),
+            menu::MenuItem::Title => next_state.set( MyState::Title ),
+            _ => (),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
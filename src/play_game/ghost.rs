@@ -0,0 +1,221 @@
+use super::*;
+use minimap::MinimapTrait;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//迷路毎のベストタイムの操作の記録（迷路の種が同じなら同じ迷路・同じスタートの向きになる）
+#[derive( Serialize, Deserialize )]
+pub struct GhostRun
+{   map_seed: u64,                        //ステージの迷路の乱数の種
+    time    : Duration,                   //クリアまでの時間
+    commands: Vec<replay::ReplayCommand>, //クリアまでの指示の列
+}
+
+//ゴーストのResource（記録された指示を経過時間に合わせて再現する）
+#[derive( Resource, Default )]
+pub struct Ghost
+{   commands : VecDeque<replay::ReplayCommand>, //これから再現する指示（先頭が次）
+    body     : player::Player, //ゴーストの位置と向きと行動
+    from     : ( IVec2, News ), //行動を開始した時の位置と向き
+    progress : f32,            //行動の進み具合(0.0～1.0)
+    is_active: bool,           //このステージの記録があればtrue
+    map      : map::Map,       //ゴースト専用の迷路（扉や宝箱はゴーストの指示で開く）
+    map_seed : u64,            //ステージの迷路の乱数の種（回転床の向きを決める）
+    steps    : u32,            //ゴーストの歩数（回転床の向きを決める）
+}
+
+impl Ghost
+{   //指示をゴースト専用の迷路で実行する（行動を開始できたらtrue）
+    fn apply( &mut self, command: replay::PlayerCommand ) -> bool
+    {   match command
+        {   replay::PlayerCommand::Action ( action ) =>
+            {   let news = match action
+                {   player::InAction::Forward  => self.body.direction,
+                    player::InAction::Backward => self.body.direction.back(),
+                    _ => return self.body.start_action( action, &self.map ),
+                };
+
+                //記録では進めたのに扉で止まるなら、イベントなどで開いていたので開けて進む
+                let is_started = self.body.start_action( action, &self.map )
+                    || self.map.open_door( self.body.position + news ) && self.body.start_action( action, &self.map );
+                if is_started { self.steps += 1 }
+                is_started
+            }
+            replay::PlayerCommand::Interact =>
+            {   //記録されているのは開けられた時だけなので、正面の扉を開ける（宝箱やイベントは通り道に関わらない）
+                let front = self.body.position + self.body.direction;
+                self.map.open_door( front );
+                false
+            }
+            replay::PlayerCommand::Select ( _ ) => false,
+        }
+    }
+
+    //行動を終える（一マス進み終えたら、Playerと同じく回転床とテレポーターが働く。落とし穴は無視する）
+    fn finish_action( &mut self )
+    {   let action = std::mem::replace( &mut self.body.in_action, player::InAction::Stop );
+        if action != player::InAction::Forward && action != player::InAction::Backward { return }
+
+        let effect = traps::resolve( &self.map, self.body.position, self.map_seed, self.steps );
+        if let Some ( direction ) = effect.turn { self.body.direction = direction }
+        if let Some ( dest ) = effect.warp { self.body.position = dest }
+    }
+}
+
+//ゴーストの3DオブジェクトのComponent
+#[derive( Component )]
+pub struct GhostEntity;
+
+//ミニマップ上のゴーストの三角形のComponent
+#[derive( Component )]
+pub struct GhostMarker;
+
+//ゴーストの設定
+const COLOR_SPRITE_MINIMAP_GHOST : Color = Color::rgba( 0.4, 0.8, 1.0, 0.6 );
+const RADIUS_SPRITE_MINIMAP_GHOST: f32 = PIXELS_PER_GRID * 0.3;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//保存済みのゴーストを読み出す（無い・壊れている場合は空）
+fn load() -> Vec<GhostRun>
+{   let Some ( text ) = storage::load( STORAGE_KEY_GHOSTS ) else { return Vec::new() };
+    ron::from_str( &text ).unwrap_or_else
+    (   | e |
+        {   warn!( "Broken ghost data: {e}" );
+            Vec::new()
+        }
+    )
+}
+
+//クリアした記録がその迷路のベストタイムなら、ゴーストとして保存する
+pub fn save_if_best( map_seed: u64, time: Duration, stage: i32, recorder: &replay::Recorder )
+{   let mut runs = load();
+    if runs.iter().any( | run | run.map_seed == map_seed && run.time <= time ) { return }
+
+    let commands = recorder.0.commands.iter().filter( | command | command.stage == stage ).copied().collect();
+    runs.retain( | run | run.map_seed != map_seed );
+    runs.push( GhostRun { map_seed, time, commands } );
+
+    let result = ron::to_string( &runs ).map_err( | e | e.to_string() )
+        .and_then( | text | storage::save( STORAGE_KEY_GHOSTS, &text ) );
+    if let Err ( e ) = result { warn!( "Failed to save the ghost: {e}" ) }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//新しく作った迷路をゴースト専用に写す（CONTINUEで開いた宝箱等を復元する前に行う）
+pub fn copy_map
+(   mut ghost: ResMut<Ghost>,
+    map: Res<map::Map>,
+)
+{   ghost.map = map.clone();
+}
+
+//ステージ開始時に、この迷路のベストタイムのゴーストをspawnする
+//（Playerと同じスタート地点と向きから始め、CONTINUEなら経過時間まで早送りする）
+type WithGhostOrMarker = Or<( With<GhostEntity>, With<GhostMarker> )>;
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_entity
+(   qry_ghost: Query<Entity, WithGhostOrMarker>,
+    mut ghost: ResMut<Ghost>,
+    player: Res<player::Player>,
+    stage: Res<stage::Stage>,
+    record: Res<hud::PlayRecord>,
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials_standard: ResMut<Assets<StandardMaterial>>,
+    mut materials_color: ResMut<Assets<ColorMaterial>>,
+)
+{   //既存のゴーストがあれば削除する
+    qry_ghost.for_each( | id | cmds.entity( id ).despawn_recursive() );
+    let map = std::mem::take( &mut ghost.map );
+    *ghost = Ghost { map, ..default() };
+
+    let map_seed = stage.map_seed();
+    let Some ( run ) = load().into_iter().find( | run | run.map_seed == map_seed ) else { return };
+
+    ghost.commands = run.commands.into();
+    ghost.body = player::Player { position: player.position, direction: player.direction, ..default() };
+    ghost.is_active = true;
+    ghost.map_seed = map_seed;
+
+    //経過時間までの指示は一気に進める
+    while let Some ( next ) = ghost.commands.front().copied()
+    {   if next.time >= record.stage_elapsed { break }
+        ghost.commands.pop_front();
+        ghost.apply( next.command );
+        ghost.finish_action();
+    }
+    ghost.from = ( ghost.body.position, ghost.body.direction );
+    ghost.progress = 1.0;
+
+    //ゴーストの姿をspawnする
+    let translation = ghost.body.position.to_3dxz();
+    let rotation = ghost.body.direction.to_quat_y();
+    cmds.spawn( ( PbrBundle::default(), GhostEntity ) )
+    .insert( materials_standard.add( Color::NONE.into() ) ) //透明
+    .insert( Transform::from_translation( translation ).with_rotation( rotation ) )
+    .with_children
+    (   | mut cmds |
+        cmds.spawn_ghost_figure( &mut meshes, &mut materials_standard )
+    );
+
+    //ミニマップにゴーストの三角形をspawnする
+    let triangle = MaterialMesh2dBundle
+    {   mesh: meshes.add( shape::RegularPolygon::new( RADIUS_SPRITE_MINIMAP_GHOST, 3 ).into() ).into(),
+        material: materials_color.add( ColorMaterial::from( COLOR_SPRITE_MINIMAP_GHOST ) ),
+        ..default()
+    };
+    let translation = ghost.body.position.to_minimap_center().truncate().extend( DEPTH_SPRITE_MINIMAP_GHOST );
+    let rotation = ghost.body.direction.to_quat_z();
+    cmds.spawn( ( triangle, GhostMarker ) )
+    .insert( Transform::from_translation( translation ).with_rotation( rotation ) )
+    ;
+}
+
+//経過時間に合わせてゴーストを動かす
+#[allow(clippy::type_complexity)]
+pub fn move_ghost
+(   mut qry_ghost: Query<&mut Transform, ( With<GhostEntity>, Without<GhostMarker> )>,
+    mut qry_marker: Query<&mut Transform, ( With<GhostMarker>, Without<GhostEntity> )>,
+    mut ghost: ResMut<Ghost>,
+    record: Res<hud::PlayRecord>,
+    time: Res<Time>,
+)
+{   if ! ghost.is_active { return }
+    let Ok ( mut transform ) = qry_ghost.get_single_mut() else { return };
+    let Ok ( mut triangle ) = qry_marker.get_single_mut() else { return };
+
+    //行動が終わっていて、次の指示の時刻になったら開始する（扉と宝箱はゴースト専用の迷路で開ける）
+    if ghost.body.is_stop()
+    {   let Some ( next ) = ghost.commands.front().copied() else { return };
+        if next.time > record.stage_elapsed { return }
+        ghost.commands.pop_front();
+
+        ghost.from = ( ghost.body.position, ghost.body.direction );
+        if ! ghost.apply( next.command ) { return }
+        ghost.progress = 0.0;
+    }
+
+    //Playerと同じ速さで進める
+    let coef = if ghost.body.is_turn() { PLAYER_TURN_COEF } else { PLAYER_MOVE_COEF };
+    ghost.progress = ( ghost.progress + time.delta().as_secs_f32() * coef ).min( 1.0 );
+    if ghost.progress >= 1.0 { ghost.finish_action() }
+
+    //開始時と終了時の位置と向きを補間する
+    let t = ghost.progress;
+    let ( from_position, from_direction ) = ghost.from;
+    let ( to_position, to_direction ) = ( ghost.body.position, ghost.body.direction );
+
+    transform.translation = from_position.to_3dxz().lerp( to_position.to_3dxz(), t );
+    transform.rotation = from_direction.to_quat_y().slerp( to_direction.to_quat_y(), t );
+
+    let from_minimap = from_position.to_minimap_center().truncate();
+    let to_minimap = to_position.to_minimap_center().truncate();
+    triangle.translation = from_minimap.lerp( to_minimap, t ).extend( DEPTH_SPRITE_MINIMAP_GHOST );
+    triangle.rotation = from_direction.to_quat_z().slerp( to_direction.to_quat_z(), t );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
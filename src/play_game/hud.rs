@@ -0,0 +1,163 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//プレイ記録のResource
+#[derive( Resource, Default )]
+pub struct PlayRecord
+{   pub steps        : u32,      //歩数
+    pub turns        : u32,      //旋回数
+    pub elapsed      : Duration, //経過時間(ポーズ中は進まない)
+    pub stage_steps  : u32,      //今のステージの歩数
+    pub stage_elapsed: Duration, //今のステージの経過時間
+}
+
+impl PlayRecord
+{   //開始できた行動を数える
+    pub fn count_action( &mut self, action: player::InAction )
+    {   match action
+        {   player::InAction::Forward  | player::InAction::Backward =>
+            {   self.steps       += 1;
+                self.stage_steps += 1;
+            }
+            player::InAction::TurnRight | player::InAction::TurnLeft => self.turns += 1,
+            player::InAction::Stop => (),
+        }
+    }
+
+    //ステージ毎の記録を0に戻す
+    pub fn reset_stage( &mut self )
+    {   self.stage_steps   = 0;
+        self.stage_elapsed = Duration::ZERO;
+    }
+}
+
+//HUDの項目
+#[derive( Clone, Copy )]
+enum HudItem { Stage, Hp, Position, Heading, Steps, Turns, Time, Replay }
+
+//HUDのtextのComponent
+#[derive( Component )]
+pub struct HudText ( HudItem );
+
+//HUDの設定（項目、見出し、HUDの小窓の左上からの行）
+counted_array!
+(   const HUD_ITEMS: [ ( HudItem, &str, i32 ); _ ] =
+    [   ( HudItem::Stage   , "STAGE ", 0 ),
+        ( HudItem::Hp      , "HP    ", 1 ),
+        ( HudItem::Position, "POS   ", 2 ),
+        ( HudItem::Heading , "DIR   ", 3 ),
+        ( HudItem::Steps   , "STEP  ", 5 ),
+        ( HudItem::Turns   , "TURN  ", 6 ),
+        ( HudItem::Time    , "TIME  ", 8 ),
+        ( HudItem::Replay  , "PLAY  ", 9 ),
+    ]
+);
+const FONT_SIZE_HUD    : f32 = PIXELS_PER_GRID * 0.5;
+const COLOR_HUD_CAPTION: Color = Color::TEAL;
+const COLOR_HUD_VALUE  : Color = Color::SILVER;
+const NA_HUD_VALUE     : &str = "---";
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ゲーム開始時にプレイ記録を初期化する
+pub fn reset_record( mut record: ResMut<PlayRecord> )
+{   *record = PlayRecord::default();
+}
+
+//経過時間を進める
+pub fn tick_elapsed
+(   mut record: ResMut<PlayRecord>,
+    time: Res<Time>,
+)
+{   record.elapsed       += time.delta();
+    record.stage_elapsed += time.delta();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//HUDをspawnする
+pub fn spawn_hud
+(   mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+)
+{   let font = asset_svr.load( ASSETS_FONT_PRESSSTART2P_REGULAR );
+    let adjust = Vec2::X * PIXELS_PER_GRID / 2.0;
+
+    for ( item, caption, row ) in HUD_ITEMS
+    {   let sections = vec!
+        [   TextSection
+            {   value: caption.to_string(),
+                style: TextStyle { font: font.clone(), font_size: FONT_SIZE_HUD, color: COLOR_HUD_CAPTION },
+            },
+            TextSection
+            {   value: NA_HUD_VALUE.to_string(),
+                style: TextStyle { font: font.clone(), font_size: FONT_SIZE_HUD, color: COLOR_HUD_VALUE },
+            },
+        ];
+        let vec2 = ( SCREEN_FRAME.hud.zero + IVec2::Y * row ).to_screen_pixel() - adjust;
+        let vec3 = vec2.extend( DEPTH_SPRITE_GAME_FRAME + 1.0 );
+
+        cmds.spawn( ( Text2dBundle::default(), HudText ( item ) ) )
+        .insert( Text { sections, ..default() } )
+        .insert( Anchor::CenterLeft )
+        .insert( Transform::from_translation( vec3 ) )
+        ;
+    }
+}
+
+//HUDの表示を更新する（元の値が変わった項目だけ書式化して書き換える）
+pub fn update_hud
+(   mut qry_text: Query<( &mut Text, Ref<HudText> )>,
+    record: Res<PlayRecord>,
+    stage: Res<stage::Stage>,
+    player: Res<player::Player>,
+    stats: Res<stats::PlayerStats>,
+    opt_playback: Option<Res<replay::Playback>>,
+    mut last_keys: Local<[ Option<( i64, i64 )>; HUD_ITEMS.len() ]>,
+)
+{   for ( mut text, hud_text ) in qry_text.iter_mut()
+    {   let item = hud_text.0;
+
+        //項目の元になる値（Timeは秒単位）。前回と同じなら書式化もしない
+        let key = match item
+        {   HudItem::Stage    => ( stage.number as i64, 0 ),
+            HudItem::Hp       => ( stats.status.hp as i64, stats.status.max_hp as i64 ),
+            HudItem::Position => ( player.position.x as i64, player.position.y as i64 ),
+            HudItem::Heading  => ( player.direction as i64, 0 ),
+            HudItem::Steps    => ( record.steps as i64, 0 ),
+            HudItem::Turns    => ( record.turns as i64, 0 ),
+            HudItem::Time     => ( record.elapsed.as_secs() as i64, 0 ),
+            HudItem::Replay   => opt_playback.as_ref()
+                .map_or( ( 0, 0 ), | playback | ( 1, playback.speed().to_bits() as i64 ) ),
+        };
+        let last_key = &mut last_keys[ item as usize ];
+        if ! hud_text.is_added() && *last_key == Some ( key ) { continue }
+        *last_key = Some ( key );
+
+        let value = match item
+        {   HudItem::Stage    => format!( "{}", stage.number ),
+            HudItem::Hp       => format!( "{}/{}", stats.status.hp, stats.status.max_hp ),
+            HudItem::Position => format!( "{},{}", player.position.x, player.position.y ),
+            HudItem::Heading  => match player.direction
+            {   News::North => "NORTH",
+                News::East  => "EAST",
+                News::West  => "WEST",
+                News::South => "SOUTH",
+            }.to_string(),
+            HudItem::Steps    => format!( "{}", record.steps ),
+            HudItem::Turns    => format!( "{}", record.turns ),
+            HudItem::Time     =>
+            {   let secs = record.elapsed.as_secs();
+                format!( "{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60 )
+            }
+            HudItem::Replay   => opt_playback.as_ref()
+                .map_or( NA_HUD_VALUE.to_string(), | playback | format!( "x{}", playback.speed() ) ),
+        };
+        text.sections[ 1 ].value = value;
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
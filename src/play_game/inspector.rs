@@ -0,0 +1,100 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//debug用：フリーカメラで指したマスの情報を表示するtext UIのComponent
+#[derive( Component )]
+pub struct InspectorText;
+
+//text UIの設定
+const FONT_SIZE_INSPECTOR : f32 = PIXELS_PER_GRID * 0.5;
+const COLOR_TEXT_INSPECTOR: Color = Color::YELLOW;
+const INSPECTOR_RAY_STEP  : f32 = 0.02;  //マス目を辿る刻み幅
+const INSPECTOR_RAY_LENGTH: f32 = 100.0; //マス目を辿る最大距離
+const GROUND_HEIGHT: f32 = -0.5; //地面の高さ
+const WALL_TOP     : f32 =  0.5; //壁の上面の高さ
+
+//text UIをspawnする（初期状態は非表示）
+pub fn spawn_text
+(   mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+)
+{   let style = TextStyle
+    {   font     : asset_svr.load( ASSETS_FONT_PRESSSTART2P_REGULAR ),
+        font_size: FONT_SIZE_INSPECTOR,
+        color    : COLOR_TEXT_INSPECTOR,
+    };
+    let sections = vec![ TextSection { value: String::new(), style } ];
+
+    //3D viewportの左上に置く
+    let origin = SCREEN_FRAME.viewport.origin;
+    let style = Style
+    {   position_type: PositionType::Absolute,
+        left: Val::Px( origin.x ),
+        top : Val::Px( origin.y ),
+        ..default()
+    };
+
+    cmds.spawn( ( TextBundle::default(), InspectorText ) )
+    .insert( Text { sections, ..default() } )
+    .insert( style )
+    .insert( Visibility::Hidden )
+    ;
+}
+
+//フリーカメラのカーソル位置にあるマスを調べて表示する
+pub fn inspect_cell
+(   qry_camera: Query<( &Camera, &GlobalTransform ), With<debug::FreeFlyCamera>>,
+    mut qry_text: Query<( &mut Text, &mut Visibility ), With<InspectorText>>,
+    qry_window: Query<&Window>,
+    map: Res<map::Map>,
+)
+{   let Ok ( ( camera, transform ) ) = qry_camera.get_single() else { return };
+    let Ok ( ( mut text, mut visibility ) ) = qry_text.get_single_mut() else { return };
+
+    //フリーカメラがアクティブでないなら表示しない
+    *visibility = if camera.is_active { Visibility::Inherited } else { Visibility::Hidden };
+    if ! camera.is_active { return }
+
+    //カーソルの位置から視線を求める(viewport内の相対位置を渡す)
+    let ray = qry_window.get_single().ok()
+        .and_then( | window | window.cursor_position() )
+        .zip( camera.logical_viewport_rect() )
+        .filter( | ( cursor, rect ) | rect.contains( *cursor ) )
+        .and_then( | ( cursor, rect ) | camera.viewport_to_world( transform, cursor - rect.min ) );
+
+    //視線の先のマス(壁か地面)を探す
+    let cell = ray.and_then( | ray | find_cell( &map, ray ) );
+
+    text.sections[ 0 ].value = match cell
+    {   Some ( cell ) =>
+        {   let mut names = map.flag_names( cell ).join( "\n" );
+            if let Some ( event ) = map.cell_event( cell ) { names += &format!( "\n[{}]", event.id ) }
+            format!( "CELL ({},{})\n{}", cell.x, cell.y, names )
+        }
+        None => "CELL --".to_string(),
+    };
+}
+
+//視線に沿ってマス目を辿り、最初に当たった壁か地面のマスを返す
+fn find_cell( map: &map::Map, ray: Ray ) -> Option<IVec2>
+{   let mut d = 0.0;
+    while d < INSPECTOR_RAY_LENGTH
+    {   d += INSPECTOR_RAY_STEP;
+        let point = ray.get_point( d );
+        let cell = IVec2::new( point.x.round() as i32, point.z.round() as i32 );
+
+        if point.y <= GROUND_HEIGHT
+        {   return map.is_inside( cell ).then_some( cell ); //地面
+        }
+        if point.y <= WALL_TOP && map.is_inside( cell ) && map.is_wall( cell )
+        {   return Some ( cell ); //壁
+        }
+    }
+
+    None
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
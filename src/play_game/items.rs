@@ -0,0 +1,80 @@
+use super::*;
+use bevy::
+{   asset::{ AssetLoader, LoadContext, LoadedAsset },
+    reflect::{ TypePath, TypeUuid },
+    utils::BoxedFuture,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+//アイテムの効果
+#[derive( Clone, Copy, PartialEq, Eq, Deserialize, Debug )]
+pub enum ItemEffect
+{   Heal ( i32 ),      //体力を回復する
+    RestoreMp ( i32 ), //MPを回復する
+    Damage ( i32 ),    //戦闘中にモンスターへダメージを与える
+    Key,               //鍵のかかった扉を開ける（扉の前で自動的に使う）
+}
+
+//アイテムの定義
+#[derive( Clone, Deserialize, Debug )]
+pub struct ItemDef
+{   pub id    : String,     //識別子（セーブデータやイベントの中身から参照する）
+    pub name  : String,     //表示名
+    pub effect: ItemEffect, //効果
+}
+
+//アイテム表のAsset（assets/data/*.items.ron）
+#[derive( Deserialize, TypeUuid, TypePath, Debug )]
+#[uuid = "6f0c1bb4-6a0e-4d2f-9c51-2f7f0f5c8a31"]
+pub struct ItemTable
+{   pub items: Vec<ItemDef>, //アイテムの定義
+}
+
+impl ItemTable
+{   //idからアイテムの定義を探す
+    pub fn get( &self, id: &str ) -> Option<&ItemDef>
+    {   self.items.iter().find( | item | item.id == id )
+    }
+}
+
+//扉の鍵のid（迷路の生成が扉と一緒に置く、鍵の宝箱から出る）
+pub const ITEM_ID_KEY: &str = "key";
+
+//アイテム表のAssetLoader
+#[derive( Default )]
+pub struct ItemTableLoader;
+
+impl AssetLoader for ItemTableLoader
+{   fn load<'a>
+    (   &'a self,
+        bytes: &'a [ u8 ],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>>
+    {   Box::pin
+        (   async move
+            {   let table: ItemTable = ron::de::from_bytes( bytes )?;
+                load_context.set_default_asset( LoadedAsset::new( table ) );
+                Ok ( () )
+            }
+        )
+    }
+
+    fn extensions( &self ) -> &[ &str ]
+    {   &[ "items.ron" ]
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//事前ロード済みのアイテム表を取り出す
+pub fn table<'a>
+(   asset_svr: &Res<AssetServer>,
+    tables: &'a Res<Assets<ItemTable>>,
+) -> Option<&'a ItemTable>
+{   tables.get( &asset_svr.load( ASSETS_DATA_ITEMS ) )
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -3,17 +3,29 @@ use super::*;
 //internal submodules
 mod maze_a;
 mod maze_b;
+mod special_cells;
 
 ////////////////////////////////////////////////////////////////////////////////
 
 //MapのResource
-#[derive( Resource )]
+#[derive( Resource, Clone )]
 pub struct Map
 {   pub rng: rand::prelude::StdRng, //専用乱数発生器
     matrix: Vec<Vec<Flag>>,         //map
     pub start: IVec2,               //スタート位置
+    pub goal : IVec2,               //ゴール位置
+    width : i32,                    //マップの横幅(Grid)
+    height: i32,                    //マップの縦幅(Grid)
+    changed: Vec<IVec2>,            //ゲーム中に状態が変わったマス
+    teleports: HashMap<IVec2, IVec2>, //テレポーターの飛ばし先
+    doors: Vec<IVec2>,              //鍵のかかった扉（スタートに近い順。添字が扉の番号）
+    events: HashMap<IVec2, cell_events::EventDef>, //マスに置いたイベント
 }
 
+//マスの状態が変わったことを知らせるEvent
+#[derive( Event )]
+pub struct CellChanged ( pub IVec2 );
+
 //マスの情報
 #[derive( Clone )]
 struct Flag ( u128 );
@@ -21,9 +33,7 @@ struct Flag ( u128 );
 //Map::default()の定義
 impl Default for Map
 {   fn default() -> Self
-    {   let seed_dev = 1234567890;
-        let seed_rel = || rand::thread_rng().gen::<u64>();
-        let seed = if misc::DEBUG() { seed_dev } else { seed_rel() };
+    {   let seed = 0; //ステージ開始時にStageの種で初期化し直す
 
         let cell = Flag ( BIT_CELL_UNDEF );
         let column = vec![ cell  ; MAP_GRIDS_HEIGHT as usize ];
@@ -33,18 +43,89 @@ impl Default for Map
         {   rng  : StdRng::seed_from_u64( seed ),
             matrix,
             start: IVec2::default(),
+            goal : IVec2::default(),
+            width : MAP_GRIDS_WIDTH,
+            height: MAP_GRIDS_HEIGHT,
+            changed: Vec::new(),
+            teleports: HashMap::new(),
+            doors: Vec::new(),
+            events: HashMap::new(),
         }
     }
 }
 
 //マス目の状態を表すビット(フラグは128個まで)
-const BIT_CELL_UNDEF      : u128 = 0b000000; //未定義
-const BIT_CELL_SPACE      : u128 = 0b000001; //地形：空地
-const BIT_CELL_WALL       : u128 = 0b000010; //地形：壁
-const BIT_FLAG_FOOTPRINTS : u128 = 0b000100; //フラグ：足跡
-const BIT_FLAG_NOENTRY    : u128 = 0b001000; //フラグ：進入禁止
-const BIT_FLAG_DEADEND    : u128 = 0b010000; //フラグ：袋小路
-const BIT_FLAG_LOCKEDCHEST: u128 = 0b100000; //フラグ：袋小路
+pub const BIT_CELL_UNDEF      : u128 = 0b00000000000000000000; //未定義
+pub const BIT_CELL_SPACE      : u128 = 0b00000000000000000001; //地形：空地
+pub const BIT_CELL_WALL       : u128 = 0b00000000000000000010; //地形：壁
+pub const BIT_FLAG_FOOTPRINTS : u128 = 0b00000000000000000100; //フラグ：足跡
+pub const BIT_FLAG_NOENTRY    : u128 = 0b00000000000000001000; //フラグ：進入禁止
+pub const BIT_FLAG_DEADEND    : u128 = 0b00000000000000010000; //フラグ：袋小路
+pub const BIT_FLAG_LOCKEDCHEST: u128 = 0b00000000000000100000; //フラグ：鍵付き宝箱
+pub const BIT_FLAG_EXPLORED   : u128 = 0b00000000000001000000; //フラグ：踏査済み（ミニマップに表示する）
+pub const BIT_FLAG_OPENEDCHEST: u128 = 0b00000000000010000000; //フラグ：開いた宝箱
+pub const BIT_FLAG_GOAL       : u128 = 0b00000000000100000000; //フラグ：ゴール（下り階段）
+pub const BIT_FLAG_STAIRS     : u128 = 0b00000000001000000000; //フラグ：上り階段（スタート地点）
+pub const BIT_FLAG_LOCKEDDOOR : u128 = 0b00000000010000000000; //フラグ：鍵のかかった扉
+pub const BIT_FLAG_OPENEDDOOR : u128 = 0b00000000100000000000; //フラグ：開いた扉
+pub const BIT_FLAG_PIT        : u128 = 0b00000001000000000000; //フラグ：落とし穴（ダメージを受ける）
+pub const BIT_FLAG_TELEPORTER : u128 = 0b00000010000000000000; //フラグ：テレポーター（つながったマスへ飛ばされる）
+pub const BIT_FLAG_SPINNER    : u128 = 0b00000100000000000000; //フラグ：回転床（向きがでたらめに変わる）
+pub const BIT_FLAG_DARKZONE   : u128 = 0b00001000000000000000; //フラグ：暗闇（ミニマップが見えなくなる）
+pub const BIT_FLAG_DISCOVERED : u128 = 0b00010000000000000000; //フラグ：罠を見つけた（ミニマップに表示する）
+pub const BIT_FLAG_EVENT      : u128 = 0b00100000000000000000; //フラグ：イベントのマス
+pub const BIT_FLAG_EVENTDONE  : u128 = 0b01000000000000000000; //フラグ：一度だけのイベントが済んだ
+
+//地形と、迷路作成・表示のための内部的なフラグ
+//これ以外のフラグはゲームの要素としてミニマップにマーカーが表示される
+const MASK_INTERNAL_FLAGS: u128 = BIT_CELL_SPACE
+                                | BIT_CELL_WALL
+                                | BIT_FLAG_FOOTPRINTS
+                                | BIT_FLAG_NOENTRY
+                                | BIT_FLAG_DEADEND
+                                | BIT_FLAG_EXPLORED
+                                | BIT_FLAG_DISCOVERED
+                                | BIT_FLAG_EVENT //イベントは起きるまで分からない
+                                | BIT_FLAG_EVENTDONE;
+
+//罠と仕掛け（見つけるまではミニマップにマーカーを表示しない）
+const MASK_HIDDEN_FLAGS: u128 = BIT_FLAG_PIT
+                              | BIT_FLAG_TELEPORTER
+                              | BIT_FLAG_SPINNER
+                              | BIT_FLAG_DARKZONE;
+
+//フラグの名前（debug用の表示に使う）
+counted_array!
+(   const FLAG_NAMES: [ ( u128, &str ); _ ] =
+    [   ( BIT_CELL_SPACE      , "SPACE"       ),
+        ( BIT_CELL_WALL       , "WALL"        ),
+        ( BIT_FLAG_FOOTPRINTS , "FOOTPRINTS"  ),
+        ( BIT_FLAG_NOENTRY    , "NOENTRY"     ),
+        ( BIT_FLAG_DEADEND    , "DEADEND"     ),
+        ( BIT_FLAG_LOCKEDCHEST, "LOCKEDCHEST" ),
+        ( BIT_FLAG_EXPLORED   , "EXPLORED"    ),
+        ( BIT_FLAG_OPENEDCHEST, "OPENEDCHEST" ),
+        ( BIT_FLAG_GOAL       , "GOAL"        ),
+        ( BIT_FLAG_STAIRS     , "STAIRS"      ),
+        ( BIT_FLAG_LOCKEDDOOR , "LOCKEDDOOR"  ),
+        ( BIT_FLAG_OPENEDDOOR , "OPENEDDOOR"  ),
+        ( BIT_FLAG_PIT        , "PIT"         ),
+        ( BIT_FLAG_TELEPORTER , "TELEPORTER"  ),
+        ( BIT_FLAG_SPINNER    , "SPINNER"     ),
+        ( BIT_FLAG_DARKZONE   , "DARKZONE"    ),
+        ( BIT_FLAG_DISCOVERED , "DISCOVERED"  ),
+        ( BIT_FLAG_EVENT      , "EVENT"       ),
+        ( BIT_FLAG_EVENTDONE  , "EVENTDONE"   ),
+    ]
+);
+
+//イベントの物（宝箱など）のEntityに印をつけるComponent
+#[derive( Component )]
+pub struct ObjectEntity ( IVec2 );
+
+//扉のEntityに印をつけるComponent
+#[derive( Component )]
+pub struct DoorEntity ( IVec2 );
 
 //Mapの全Entityの親になるEntityに印をつけるComponent
 #[derive( Component )]
@@ -55,9 +136,9 @@ pub struct MapZeroEntity;
 //Mapのメソッド
 impl Map
 {   //ユーティリティ
-    fn is_inside( &self, cell: IVec2 ) -> bool
-    {   MAP_GRIDS_X_RANGE.contains( &cell.x ) &&
-        MAP_GRIDS_Y_RANGE.contains( &cell.y )
+    pub fn is_inside( &self, cell: IVec2 ) -> bool
+    {   self.x_range().contains( &cell.x ) &&
+        self.y_range().contains( &cell.y )
     }
     fn matrix_mut( &mut self, IVec2 { x, y }: IVec2 ) -> &mut Flag
     {   &mut self.matrix[ x as usize ][ y as usize ]
@@ -96,11 +177,70 @@ impl Map
     {   if ! self.is_inside( cell ) { return }
         self.matrix_mut( cell ).0 |= BIT_FLAG_LOCKEDCHEST;
     }
+    fn add_flag_explored( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_EXPLORED;
+    }
+    fn add_flag_goal( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_GOAL;
+    }
+    fn add_flag_stairs( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_STAIRS;
+    }
+    fn add_flag_lockeddoor( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_LOCKEDDOOR;
+    }
+    fn add_flag_pit( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_PIT;
+    }
+    fn add_flag_teleporter( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_TELEPORTER;
+    }
+    fn add_flag_spinner( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_SPINNER;
+    }
+    fn add_flag_darkzone( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_DARKZONE;
+    }
+    fn add_flag_discovered( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_DISCOVERED;
+    }
+    fn add_flag_event( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_EVENT;
+    }
+    fn add_flag_eventdone( &mut self, cell: IVec2 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 |= BIT_FLAG_EVENTDONE;
+    }
+
+    //指定の位置のフラグを取り除く
+    fn remove_flags( &mut self, cell: IVec2, bits: u128 )
+    {   if ! self.is_inside( cell ) { return }
+        self.matrix_mut( cell ).0 &= ! bits;
+    }
+
+    //マップの大きさを変える（中身は未定義になる）
+    fn resize( &mut self, size: IVec2 )
+    {   let size = size.clamp( IVec2::splat( 5 ), IVec2::new( MAP_GRIDS_WIDTH, MAP_GRIDS_HEIGHT ) );
+        ( self.width, self.height ) = ( size.x, size.y );
+
+        let column = vec![ Flag ( BIT_CELL_UNDEF ); self.height as usize ];
+        self.matrix = vec![ column; self.width as usize ];
+    }
 
     //袋小路を探してフラグを付加する
     fn search_deadend( &mut self )
-    {   for x in MAP_GRIDS_X_RANGE
-        {   for y in MAP_GRIDS_Y_RANGE
+    {   for x in self.x_range()
+        {   for y in self.y_range()
             {   let cell = IVec2::new( x, y );
 
                 //空地じゃないなら（壁なら）
@@ -117,11 +257,118 @@ impl Map
             }
         }
     }
+
+    //fromから最も遠い袋小路を探す（見つからなければfromを返す）
+    fn find_farthest_deadend( &self, from: IVec2 ) -> IVec2
+    {   let mut farthest = from;
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert( from );
+        queue.push_back( from );
+
+        //幅優先探索なので、最後に見つかった袋小路が最も遠い
+        while let Some ( cell ) = queue.pop_front()
+        {   if self.is_deadend( cell ) && cell != from { farthest = cell }
+
+            for news in NEWS
+            {   let next = cell + news;
+                if ! self.is_space( next ) || ! visited.insert( next ) { continue }
+                queue.push_back( next );
+            }
+        }
+
+        farthest
+    }
+
+    //fromから進入禁止のマスを通らずに行けるマスを返す
+    fn reachable_cells( &self, from: IVec2 ) -> HashSet<IVec2>
+    {   let mut visited = HashSet::from( [ from ] );
+        let mut queue = VecDeque::from( [ from ] );
+
+        while let Some ( cell ) = queue.pop_front()
+        {   for news in NEWS
+            {   let next = cell + news;
+                if ! self.is_space( next ) || self.is_noentry( next ) { continue }
+                if visited.insert( next ) { queue.push_back( next ) }
+            }
+        }
+
+        visited
+    }
+
+    //スタートからゴールへの道を塞ぐ扉と、その鍵を入れた宝箱のイベントを置く
+    //（鍵は、それより手前の扉を開ければ必ず取れる場所に置く）
+    fn place_doors( &mut self, count: usize )
+    {   if count == 0 { return }
+        let Some ( route ) = self.find_path( self.start, self.goal ) else { return };
+
+        //扉の候補：経路上のまっすぐな通路で、塞ぐとゴールへ行けなくなるマス
+        let mut candidates = Vec::new();
+        for &cell in route.iter().take( route.len().saturating_sub( 1 ) ) //ゴールは除く
+        {   let sides = self.get_sides_space( cell );
+            if sides.len() != 2 || sides[ 0 ].back() != sides[ 1 ] { continue }
+
+            self.add_flag_noentry( cell );
+            if self.find_path( self.start, self.goal ).is_none() { candidates.push( cell ) }
+            self.remove_flags( cell, BIT_FLAG_NOENTRY );
+        }
+
+        //経路を扉の数で区切り、区間ごとに一つ選ぶ（扉が固まらないように）
+        let mut doors = Vec::new();
+        for i in 0..count
+        {   let range = candidates.len() * i / count..candidates.len() * ( i + 1 ) / count;
+            if range.is_empty() { continue }
+            doors.push( candidates[ self.rng.gen_range( range ) ] );
+        }
+        doors.iter().for_each( | &door | self.add_flag_noentry( door ) );
+
+        //手前の扉から順に、その扉より手前で行ける袋小路に鍵の宝箱を置く
+        for &door in &doors
+        {   let reachable = self.reachable_cells( self.start );
+            let mut deadends: Vec<_> = reachable.iter().copied()
+                .filter( | &cell | self.is_deadend( cell ) && cell != self.start && cell != self.goal )
+                .collect();
+            deadends.sort_by_key( | cell | ( cell.x, cell.y ) ); //HashSetの順序に依らないように
+
+            //鍵を置けなければ扉をやめる
+            if deadends.is_empty()
+            {   self.remove_flags( door, BIT_FLAG_NOENTRY );
+                continue;
+            }
+            let chest = deadends[ self.rng.gen_range( 0..deadends.len() ) ];
+            self.put_event( chest, cell_events::EventDef::key_chest() ); //宝箱は進入禁止になる
+            self.add_flag_lockeddoor( door );
+            self.doors.push( door );
+
+            //次の扉の鍵は、この扉の先にも置ける
+            self.remove_flags( door, BIT_FLAG_NOENTRY );
+        }
+
+        //扉を閉じる
+        for door in doors
+        {   if self.is_lockeddoor( door ) { self.add_flag_noentry( door ) }
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 
 //Mapのpubメソッド
+impl Map
+{   //マップの大きさ(Grid)
+    pub fn size( &self ) -> IVec2
+    {   IVec2::new( self.width, self.height )
+    }
+
+    //マップのレンジ（外壁含む）
+    pub fn x_range( &self ) -> Range<i32> { 0..self.width  }
+    pub fn y_range( &self ) -> Range<i32> { 0..self.height }
+
+    //外壁を含まないレンジ
+    pub fn x_range_inner( &self ) -> Range<i32> { 1..self.width  - 1 }
+    pub fn y_range_inner( &self ) -> Range<i32> { 1..self.height - 1 }
+}
+
 impl Map
 {   //cellの四方を調べて空地がある方角のVecを返す
     pub fn get_sides_space( &self, cell: IVec2 ) -> Vec< News >
@@ -159,49 +406,343 @@ impl Map
     {   if ! self.is_inside( cell ) { return false } //範囲外に空地はない(＝袋小路もない)
         self.matrix( cell ).0 & BIT_FLAG_LOCKEDCHEST != 0
     }
+    pub fn is_explored( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false } //範囲外は踏査できない
+        self.matrix( cell ).0 & BIT_FLAG_EXPLORED != 0
+    }
+    pub fn is_openedchest( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false }
+        self.matrix( cell ).0 & BIT_FLAG_OPENEDCHEST != 0
+    }
+    pub fn is_lockeddoor( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false }
+        self.matrix( cell ).0 & BIT_FLAG_LOCKEDDOOR != 0
+    }
+    pub fn is_openeddoor( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false }
+        self.matrix( cell ).0 & BIT_FLAG_OPENEDDOOR != 0
+    }
+    pub fn is_pit( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false }
+        self.matrix( cell ).0 & BIT_FLAG_PIT != 0
+    }
+    pub fn is_teleporter( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false }
+        self.matrix( cell ).0 & BIT_FLAG_TELEPORTER != 0
+    }
+    pub fn is_spinner( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false }
+        self.matrix( cell ).0 & BIT_FLAG_SPINNER != 0
+    }
+    pub fn is_darkzone( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false }
+        self.matrix( cell ).0 & BIT_FLAG_DARKZONE != 0
+    }
+    pub fn is_discovered( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false }
+        self.matrix( cell ).0 & BIT_FLAG_DISCOVERED != 0
+    }
+    pub fn is_event( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false }
+        self.matrix( cell ).0 & BIT_FLAG_EVENT != 0
+    }
+    pub fn is_eventdone( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_inside( cell ) { return false }
+        self.matrix( cell ).0 & BIT_FLAG_EVENTDONE != 0
+    }
+
+    //テレポーターの飛ばし先を返す
+    pub fn teleport_destination( &self, cell: IVec2 ) -> Option<IVec2>
+    {   self.teleports.get( &cell ).copied()
+    }
+
+    //マスに置いたイベントを返す（一度だけのイベントが済んでいればNone）
+    pub fn cell_event( &self, cell: IVec2 ) -> Option<&cell_events::EventDef>
+    {   if ! self.is_event( cell ) || self.is_eventdone( cell ) { return None }
+        self.events.get( &cell )
+    }
+
+    //マスに置いたイベントの物を返す（済んだイベントの物も返す）
+    pub fn event_object( &self, cell: IVec2 ) -> Option<cell_events::Object>
+    {   if ! self.is_event( cell ) { return None }
+        self.events.get( &cell ).and_then( | event | event.object )
+    }
+
+    //ゲームの要素を表すフラグを１ビットずつ取り出して返す（内部的なフラグは除く）
+    pub fn feature_flags( &self, cell: IVec2 ) -> Vec<u128>
+    {   if ! self.is_inside( cell ) { return Vec::new() }
+
+        let mut bits = self.matrix( cell ).0 & ! MASK_INTERNAL_FLAGS;
+        if ! self.is_discovered( cell ) { bits &= ! MASK_HIDDEN_FLAGS }
+        ( 0..u128::BITS ).map( | n | 1 << n ).filter( | bit | bits & bit != 0 ).collect()
+    }
+
+    //マスに立っているフラグの名前を返す（debug用）
+    pub fn flag_names( &self, cell: IVec2 ) -> Vec<&'static str>
+    {   if ! self.is_inside( cell ) { return Vec::new() }
+
+        let bits = self.matrix( cell ).0;
+        FLAG_NAMES.iter().filter( | ( bit, _ ) | bits & bit != 0 ).map( | ( _, name ) | *name ).collect()
+    }
+
+    //開いた宝箱のマスを返す（記録用）
+    pub fn opened_chests( &self ) -> Vec<IVec2>
+    {   self.x_range()
+        .flat_map( | x | self.y_range().map( move | y | IVec2::new( x, y ) ) )
+        .filter( | &cell | self.is_openedchest( cell ) )
+        .collect()
+    }
+
+    //開いた扉のマスを返す（セーブ用）
+    pub fn opened_doors( &self ) -> Vec<IVec2>
+    {   self.x_range()
+        .flat_map( | x | self.y_range().map( move | y | IVec2::new( x, y ) ) )
+        .filter( | &cell | self.is_openeddoor( cell ) )
+        .collect()
+    }
+
+    //罠を見つけたマスを返す（セーブ用）
+    pub fn discovered_cells( &self ) -> Vec<IVec2>
+    {   self.x_range()
+        .flat_map( | x | self.y_range().map( move | y | IVec2::new( x, y ) ) )
+        .filter( | &cell | self.is_discovered( cell ) )
+        .collect()
+    }
+
+    //一度だけのイベントが済んだマスを返す（セーブ用）
+    pub fn finished_events( &self ) -> Vec<IVec2>
+    {   self.x_range()
+        .flat_map( | x | self.y_range().map( move | y | IVec2::new( x, y ) ) )
+        .filter( | &cell | self.is_eventdone( cell ) )
+        .collect()
+    }
+
+    //踏査済みのマスを'1'、それ以外を'0'とした文字列を返す（セーブ用、列優先）
+    pub fn explored_cells( &self ) -> String
+    {   self.x_range()
+        .flat_map( | x | self.y_range().map( move | y | IVec2::new( x, y ) ) )
+        .map( | cell | if self.is_explored( cell ) { '1' } else { '0' } )
+        .collect()
+    }
+
+    //セーブデータから扉と罠とイベント（宝箱を含む）と踏査済みのマスを復元する（同じ種で作り直した迷路に重ねる）
+    pub fn restore_cells
+    (   &mut self,
+        opened_doors: &[ IVec2 ],
+        discovered_cells: &[ IVec2 ],
+        finished_events: &[ IVec2 ],
+        explored_cells: &str,
+    )
+    {   for &cell in opened_doors
+        {   if ! self.is_lockeddoor( cell ) { continue }
+            let flag = self.matrix_mut( cell );
+            flag.0 &= ! ( BIT_FLAG_LOCKEDDOOR | BIT_FLAG_NOENTRY );
+            flag.0 |= BIT_FLAG_OPENEDDOOR;
+        }
+        for &cell in discovered_cells
+        {   self.add_flag_discovered( cell );
+        }
+        for &cell in finished_events
+        {   if self.is_event( cell ) { self.close_event( cell ) }
+        }
+
+        let cells: Vec<_> = self.x_range()
+            .flat_map( | x | self.y_range().map( move | y | IVec2::new( x, y ) ) )
+            .collect();
+        for ( cell, char ) in cells.into_iter().zip( explored_cells.chars() )
+        {   if char == '1' { self.add_flag_explored( cell ) }
+        }
+    }
+
+    //鍵のかかった扉を開ける（開けられたらtrueを返す。以後は通れる）
+    pub fn open_door( &mut self, cell: IVec2 ) -> bool
+    {   if ! self.is_lockeddoor( cell ) { return false }
+
+        let flag = self.matrix_mut( cell );
+        flag.0 &= ! ( BIT_FLAG_LOCKEDDOOR | BIT_FLAG_NOENTRY );
+        flag.0 |= BIT_FLAG_OPENEDDOOR;
+        self.changed.push( cell );
+
+        true
+    }
+
+    //番号の扉を開ける（イベント用。開けられたらtrueを返す）
+    pub fn open_numbered_door( &mut self, number: usize ) -> bool
+    {   let Some ( &door ) = self.doors.get( number ) else { return false };
+        self.open_door( door )
+    }
+
+    //一度だけのイベントを済んだことにする（宝箱なら開いた宝箱になる）
+    pub fn finish_event( &mut self, cell: IVec2 )
+    {   self.close_event( cell );
+        self.changed.push( cell );
+    }
+    fn close_event( &mut self, cell: IVec2 )
+    {   self.add_flag_eventdone( cell );
+        if self.is_lockedchest( cell )
+        {   let flag = self.matrix_mut( cell );
+            flag.0 &= ! BIT_FLAG_LOCKEDCHEST;
+            flag.0 |= BIT_FLAG_OPENEDCHEST;
+        }
+    }
+
+    //罠を見つけたことにする（初めて見つけたらtrueを返す）
+    pub fn discover( &mut self, cell: IVec2 ) -> bool
+    {   if self.is_discovered( cell ) { return false }
+
+        self.add_flag_discovered( cell );
+        self.changed.push( cell );
+
+        true
+    }
+
+    //指定の位置から四方を見通して、見えたマスに踏査済みのフラグを付加する
+    //（通路の両脇の壁も見えたものとする）
+    pub fn explore_sight( &mut self, from: IVec2 )
+    {   let explore = | map: &mut Self, cell: IVec2 |
+        {   if ! map.is_inside( cell ) || map.is_explored( cell ) { return }
+            map.add_flag_explored( cell );
+            map.changed.push( cell );
+        };
+
+        for news in NEWS
+        {   //壁に突き当たるまで見通す
+            let mut cell = from;
+            loop
+            {   //マス自身と周囲８マス
+                for x in -1..=1
+                {   for y in -1..=1
+                    {   explore( self, cell + IVec2::new( x, y ) );
+                    }
+                }
+
+                cell += news;
+                if ! self.is_space( cell ) { break }
+            }
+        }
+    }
+
+    //幅優先探索でfromからtoまでの最短経路を返す（進入禁止のマスは通らない）
+    //経路はfromを含まずtoを含む。たどり着けないならNone
+    pub fn find_path( &self, from: IVec2, to: IVec2 ) -> Option<Vec<IVec2>>
+    {   if ! self.is_space( to ) || self.is_noentry( to ) { return None }
+
+        //探索の準備（マス目ごとに一つ手前のマスを記録する）
+        let mut came_from = HashMap::new();
+        let mut queue = VecDeque::new();
+        came_from.insert( from, from );
+        queue.push_back( from );
+
+        //探索ループ
+        while let Some ( cell ) = queue.pop_front()
+        {   //目的地に着いたら、記録を逆にたどって経路を作る
+            if cell == to
+            {   let mut route = Vec::new();
+                let mut back = to;
+                while back != from
+                {   route.push( back );
+                    back = came_from[ &back ];
+                }
+                route.reverse();
+
+                return Some ( route )
+            }
+
+            //四方の進入できるマスを探索対象に加える
+            for news in NEWS
+            {   let next = cell + news;
+                if came_from.contains_key( &next ) { continue }
+                if ! self.is_space( next ) || self.is_noentry( next ) { continue }
+
+                came_from.insert( next, cell );
+                queue.push_back( next );
+            }
+        }
+
+        None //たどり着けない
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 
-//新しいMapデータを作る
-pub fn make_new_data( mut map: ResMut<Map> )
-{   //初期化する
-    map.fill_walls();
-    map.start = if misc::DEBUG()
-    {   //DEBUG: 開始位置をマップ中央に固定
-        IVec2::new( MAP_GRIDS_WIDTH / 2, MAP_GRIDS_HEIGHT / 2 )
-    }
-    else
-    {   //迷路生成関数に任せる（開始位置を指定しない）
-        IVec2::NEG_ONE 
-    };
-
-    //迷路を作る
-    match map.rng.gen_range( 0..3 )
-    {   0 => { map.build_maze_a_1().build_maze_b(); },
-        1 => { map.build_maze_a_2().build_maze_b(); },
-        2 => { map.build_maze_b(); },
-        _ => (),
-    }
-
-    //迷路の構造を解析してフラグを付加する＜仮＞
-    map.search_deadend();
-
-    //イベントオブジェクトのフラグを追加する＜仮＞
-    for x in MAP_GRIDS_X_RANGE
-    {   for y in MAP_GRIDS_Y_RANGE
-        {   let cell = IVec2::new( x, y );
-
-            if map.is_deadend( cell )
-            && map.rng.gen_bool( 1.0 / 3.0 )
-            && cell != map.start
-            {   map.add_flag_noentry( cell ); //進入禁止の目印
-                map.add_flag_lockedchest( cell ); //＜仮＞
-            }
+//Mapの生成
+impl Map
+{   //新しいMapデータを作る（大きさ、迷路の作り方、扉や罠の数はステージの難易度で決まる）
+    //（種と難易度とイベント表が同じなら同じ迷路になる）
+    pub fn generate
+    (   &mut self,
+        map_seed: u64,
+        difficulty: &stage::Difficulty,
+        events: Option<&cell_events::EventTable>,
+    )
+    {   //初期化する（乱数はステージ毎の種から作り直す）
+        self.rng = StdRng::seed_from_u64( map_seed );
+        self.resize( difficulty.size );
+        self.fill_walls();
+        self.changed.clear();
+        self.teleports.clear();
+        self.doors.clear();
+        self.events.clear();
+        self.start = if misc::DEBUG()
+        {   //DEBUG: 開始位置をマップ中央に固定
+            self.size() / 2
         }
+        else
+        {   //迷路生成関数に任せる（開始位置を指定しない）
+            IVec2::NEG_ONE 
+        };
+
+        //迷路を作る
+        let generators = difficulty.generators;
+        match generators[ self.rng.gen_range( 0..generators.len() ) ]
+        {   stage::Generator::MazeA1 => { self.build_maze_a_1().build_maze_b(); },
+            stage::Generator::MazeA2 => { self.build_maze_a_2().build_maze_b(); },
+            stage::Generator::MazeB  => { self.build_maze_b(); },
+        }
+
+        //迷路の構造を解析してフラグを付加する＜仮＞
+        self.search_deadend();
+
+        //スタートから最も遠い袋小路をゴールにする
+        self.goal = self.find_farthest_deadend( self.start );
+        let ( start, goal ) = ( self.start, self.goal );
+        self.add_flag_stairs( start );
+        self.add_flag_goal( goal );
+
+        //扉と鍵の宝箱を置く
+        self.place_doors( difficulty.doors );
+
+        //罠と仕掛けを置く（扉の後に置き、テレポーターで扉を越えられないようにする）
+        self.place_special_cells( difficulty );
+
+        //ステージのイベント表に従ってイベント（宝箱を含む）を置く
+        if let Some ( table ) = events { self.place_events( table ) }
     }
 }
 
+//ステージの新しいMapデータを作る
+pub fn make_new_data
+(   mut map: ResMut<Map>,
+    stage: Res<stage::Stage>,
+    asset_svr: Res<AssetServer>,
+    event_tables: Res<Assets<cell_events::EventTable>>,
+)
+{   let events = cell_events::table( &asset_svr, &event_tables, &stage );
+    map.generate( stage.map_seed(), stage.difficulty(), events );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ゲーム中に状態が変わったマスをEventで知らせる
+pub fn send_changed_cells
+(   mut map: ResMut<Map>,
+    mut evt_changed: EventWriter<CellChanged>,
+)
+{   if map.changed.is_empty() { return }
+
+    evt_changed.send_batch( map.changed.drain( .. ).map( CellChanged ) );
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 //迷路の3Dオブジェクトをspawnする
@@ -229,10 +770,10 @@ pub fn spawn_entity
     .insert( Transform::from_translation( Vec3::ZERO ) ) //原点
     .insert( materials.add( texture_wall_zero ) )
     .with_children
-    (   | mut cmds |
+    (   | cmds |
         {   //子は、親からの相対位置にspawnされる(XZ平面)
-            for x in MAP_GRIDS_X_RANGE
-            {    for y in MAP_GRIDS_Y_RANGE
+            for x in map.x_range()
+            {    for y in map.y_range()
                 {   //原点は親なのでスキップ
                     if x == 0 && y == 0 { continue }
 
@@ -249,16 +790,35 @@ pub fn spawn_entity
                         ;
                     }
 
-                    //宝箱
-                    if map.is_lockedchest( cell ) //＜仮＞
+                    //イベントの物（セーブデータから再開した場合は済んだイベントの物もある）
+                    if let Some ( object ) = map.event_object( cell )
+                    {   let quat = map.get_sides_space( cell )[ 0 ].to_quat_y();
+                        let is_done = map.is_eventdone( cell );
+                        cmds.spawn( ( PbrBundle::default(), ObjectEntity ( cell ) ) )
+                        .insert( materials.add( Color::NONE.into() ) ) //透明
+                        .insert( Transform::from_translation( vec3 ).with_rotation( quat ) )
+                        .with_children
+                        (   | cmds |
+                            spawn_object( cmds, object, is_done, &mut meshes, &mut materials )
+                        );
+                    }
+
+                    //鍵のかかった扉（通路をふさぐ向きに置く。開いた扉は表示しない）
+                    if map.is_lockeddoor( cell )
                     {   let quat = map.get_sides_space( cell )[ 0 ].to_quat_y();
-                        cmds.spawn_locked_chest( vec3, quat, &mut meshes, &mut materials );
+                        cmds.spawn( ( PbrBundle::default(), DoorEntity ( cell ) ) )
+                        .insert( materials.add( Color::NONE.into() ) ) //透明
+                        .insert( Transform::from_translation( vec3 ).with_rotation( quat ) )
+                        .with_children
+                        (   | mut cmds |
+                            cmds.spawn_locked_door( Vec3::ZERO, Quat::IDENTITY, &mut meshes, &mut materials )
+                        );
                     }
                 }
             }
 
             //地面も相対位置でspawnする
-            let long_side = MAP_GRIDS_WIDTH.max( MAP_GRIDS_HEIGHT ) as f32;
+            let long_side = map.size().max_element() as f32;
             let half = long_side / 2.0;
             let position = Vec3::new( half, 0.0, half ) - Vec3::ONE / 2.0;
             cmds.spawn( PbrBundle::default() )
@@ -272,4 +832,152 @@ pub fn spawn_entity
 
 ////////////////////////////////////////////////////////////////////////////////
 
-//End of code.
\ No newline at end of file
+//イベントの物の3Dオブジェクトをspawnする（済んだイベントなら開いた宝箱など）
+fn spawn_object
+(   mut cmds: &mut ChildBuilder,
+    object: cell_events::Object,
+    is_done: bool,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+)
+{   match object
+    {   cell_events::Object::Chest if is_done =>
+            cmds.spawn_opened_chest( Vec3::ZERO, Quat::IDENTITY, meshes, materials ),
+        cell_events::Object::Chest =>
+            cmds.spawn_locked_chest( Vec3::ZERO, Quat::IDENTITY, meshes, materials ),
+    }
+}
+
+//イベントが済んだ物（開けられた宝箱など）の3Dオブジェクトを差し替える
+pub fn update_objects
+(   qry_object: Query<( Entity, &ObjectEntity )>,
+    mut evt_changed: EventReader<CellChanged>,
+    map: Res<Map>,
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+)
+{   for CellChanged ( cell ) in evt_changed.iter()
+    {   if ! map.is_eventdone( *cell ) { continue }
+        let Some ( object ) = map.event_object( *cell ) else { continue };
+
+        //該当する物の中身を済んだ姿に入れ替える
+        for ( id, _ ) in qry_object.iter().filter( | ( _, entity ) | entity.0 == *cell )
+        {   cmds.entity( id )
+            .despawn_descendants()
+            .with_children
+            (   | cmds |
+                spawn_object( cmds, object, true, &mut meshes, &mut materials )
+            );
+        }
+    }
+}
+
+//開けられた扉の3Dオブジェクトを取り除く
+pub fn update_doors
+(   qry_door: Query<( Entity, &DoorEntity )>,
+    mut evt_changed: EventReader<CellChanged>,
+    map: Res<Map>,
+    mut cmds: Commands,
+)
+{   for CellChanged ( cell ) in evt_changed.iter()
+    {   if ! map.is_openeddoor( *cell ) { continue }
+
+        qry_door.iter()
+        .filter( | ( _, door ) | door.0 == *cell )
+        .for_each( | ( id, _ ) | cmds.entity( id ).despawn_recursive() );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests
+{   use super::*;
+
+    //どの種とステージでも、行ける鍵の宝箱を開けながら扉を開けていけばゴールに着ける
+    //（扉に着いた時には、その扉の鍵をもう手に入れられる）
+    #[test]
+    fn keys_are_reachable_before_doors()
+    {   let mut opened_doors = 0;
+        for seed in 0..5
+        {   for number in 1..=6
+            {   let stage = stage::Stage { number, seed };
+                if stage.difficulty().doors == 0 { continue }
+                let mut map = Map::default();
+                map.generate( stage.map_seed(), stage.difficulty(), None );
+
+                let mut keys = 0;
+                loop
+                {   let reachable = map.reachable_cells( map.start );
+                    if reachable.contains( &map.goal ) { break }
+
+                    //行けるマスに隣り合った宝箱と扉
+                    let cells: Vec<_> = map.x_range()
+                        .flat_map( | x | map.y_range().map( move | y | IVec2::new( x, y ) ) )
+                        .filter( | &cell | NEWS.iter().any( | &news | reachable.contains( &( cell + news ) ) ) )
+                        .collect();
+
+                    //鍵の入った宝箱は全部開ける
+                    let key = cell_events::EventAction::GiveItem ( items::ITEM_ID_KEY.to_string() );
+                    for &cell in &cells
+                    {   let is_key_chest = map.cell_event( cell ).is_some_and( | event | event.actions.contains( &key ) );
+                        if is_key_chest { map.finish_event( cell ); keys += 1 }
+                    }
+
+                    //扉を一つ開ける
+                    let door = cells.iter().copied().find( | &cell | map.is_lockeddoor( cell ) );
+                    let Some ( door ) = door else { panic!( "seed {seed} stage {number}: the goal is unreachable" ) };
+                    assert!( keys > 0, "seed {seed} stage {number}: no key before the door at {door}" );
+                    keys -= 1;
+                    map.open_door( door );
+                    opened_doors += 1;
+                }
+            }
+        }
+        assert!( opened_doors > 0, "no doors were placed" );
+    }
+
+    //宝箱のイベントは進入禁止で、済んだら開いた宝箱になる（もう起きない）
+    #[test]
+    fn chest_event_opens_when_finished()
+    {   let stage = stage::Stage { number: 3, seed: 0 };
+        let mut map = Map::default();
+        map.generate( stage.map_seed(), stage.difficulty(), None );
+
+        let chest = map.events.keys().copied()
+            .find( | &cell | map.event_object( cell ) == Some ( cell_events::Object::Chest ) )
+            .expect( "the key chest should be placed" );
+        assert!( map.is_noentry( chest ) );
+        assert!( map.is_lockedchest( chest ) );
+
+        map.finish_event( chest );
+        assert!( ! map.is_lockedchest( chest ) );
+        assert!( map.is_openedchest( chest ) );
+        assert!( map.cell_event( chest ).is_none() );
+        assert_eq!( map.event_object( chest ), Some ( cell_events::Object::Chest ) ); //姿は残る
+    }
+
+    //番号の扉を開けても、他の扉は閉じたまま
+    #[test]
+    fn numbered_door_opens_only_itself()
+    {   let stage = stage::Stage { number: 5, seed: 0 };
+        let mut map = Map::default();
+        map.generate( stage.map_seed(), stage.difficulty(), None );
+        assert!( map.doors.len() >= 2, "need two doors to compare" );
+
+        let doors = map.doors.clone();
+        assert!( map.open_numbered_door( 1 ) );
+        assert!( map.is_openeddoor( doors[ 1 ] ) );
+        assert!( ! map.is_noentry( doors[ 1 ] ) );
+        for ( number, &door ) in doors.iter().enumerate().filter( | ( number, _ ) | *number != 1 )
+        {   assert!( map.is_lockeddoor( door ), "door {number} was opened too" );
+            assert!( map.is_noentry( door ) );
+        }
+        assert!( ! map.open_numbered_door( doors.len() ) ); //無い番号
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
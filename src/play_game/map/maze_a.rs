@@ -8,8 +8,8 @@ impl Map
     pub fn build_maze_a_1( &mut self ) -> &mut Self
     {   //スタート地点を決める
         if self.start == IVec2::NEG_ONE
-        {   let x = self.rng.gen_range( MAP_GRIDS_X_RANGE_INNER );
-            let y = self.rng.gen_range( MAP_GRIDS_Y_RANGE_INNER );
+        {   let x = self.rng.gen_range( self.x_range_inner() );
+            let y = self.rng.gen_range( self.y_range_inner() );
             self.start = IVec2::new( x, y );
             let start = self.start;
             self.set_space( start );
@@ -31,8 +31,8 @@ impl Map
             {   let next = cell + news;
 
                 //外壁は掘れない
-                if ! MAP_GRIDS_X_RANGE_INNER.contains( &next.x )
-                || ! MAP_GRIDS_Y_RANGE_INNER.contains( &next.y ) { continue }
+                if ! self.x_range_inner().contains( &next.x )
+                || ! self.y_range_inner().contains( &next.y ) { continue }
 
                 //四方のグリッドを調べる
                 if self.is_wall( next ) && self.is_digable( next, news )
@@ -101,8 +101,8 @@ impl Map
     pub fn build_maze_a_2( &mut self ) -> &mut Self
     {   //スタート地点を決める
         if self.start == IVec2::NEG_ONE
-        {   let x = self.rng.gen_range( MAP_GRIDS_X_RANGE_INNER );
-            let y = self.rng.gen_range( MAP_GRIDS_Y_RANGE_INNER );
+        {   let x = self.rng.gen_range( self.x_range_inner() );
+            let y = self.rng.gen_range( self.y_range_inner() );
             self.start = IVec2::new( x, y );
             let start = self.start;
             self.set_space( start );
@@ -125,8 +125,8 @@ impl Map
             {   let next = cell + news;
 
                 //外壁は掘れない
-                if ! MAP_GRIDS_X_RANGE_INNER.contains( &next.x )
-                || ! MAP_GRIDS_Y_RANGE_INNER.contains( &next.y ) { continue }
+                if ! self.x_range_inner().contains( &next.x )
+                || ! self.y_range_inner().contains( &next.y ) { continue }
 
                 //四方のグリッドを調べる
                 if self.is_wall( next ) && self.is_digable( next, news )
@@ -146,9 +146,9 @@ impl Map
 
                 //外壁に達したら
                 if cell.y <= 1                    { flags.insert( News::North ); }
-                if cell.x >= MAP_GRIDS_WIDTH  - 2 { flags.insert( News::East  ); }
+                if cell.x >= self.width  - 2 { flags.insert( News::East  ); }
                 if cell.x <= 1                    { flags.insert( News::West  ); }
-                if cell.y >= MAP_GRIDS_HEIGHT - 2 { flags.insert( News::South ); }
+                if cell.y >= self.height - 2 { flags.insert( News::South ); }
 
                 if flags.len() >= 4 { break } //四方の外壁すべてに達したらループ脱出
             }
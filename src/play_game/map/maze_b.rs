@@ -8,8 +8,8 @@ impl Map
     pub fn build_maze_b( &mut self ) -> &mut Self
     {   //スタート地点を決める
         if self.start == IVec2::NEG_ONE
-        {   let x = self.rng.gen_range( MAP_GRIDS_X_RANGE_INNER );
-            let y = self.rng.gen_range( MAP_GRIDS_Y_RANGE_INNER );
+        {   let x = self.rng.gen_range( self.x_range_inner() );
+            let y = self.rng.gen_range( self.y_range_inner() );
             self.start = IVec2::new( x, y );
             let start = self.start;
             self.set_space( start );
@@ -20,8 +20,8 @@ impl Map
         loop
         {   //マップを全面走査して拡張条件を満たす壁を記録する
             digable_walls.clear();
-            for x in MAP_GRIDS_X_RANGE_INNER
-            {   for y in MAP_GRIDS_Y_RANGE_INNER
+            for x in self.x_range_inner()
+            {   for y in self.y_range_inner()
                 {   let cell = IVec2::new( x, y );
                     if self.is_expandable( cell )
                     {   digable_walls.push( cell )
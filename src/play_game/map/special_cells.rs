@@ -0,0 +1,115 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//罠と仕掛けとイベントの配置の設定
+const SAFE_RADIUS_FROM_START: i32 = 3; //スタートからこの距離(マス)未満には置かない
+const TELEPORT_MIN_DISTANCE : i32 = 6; //テレポーターの飛ばし先までの最短距離(マス)
+const DARKZONE_RADIUS       : i32 = 2; //暗闇の広さ（中心からの距離）
+
+//Mapのメソッド（罠と仕掛けとイベントの配置）
+impl Map
+{   //難易度に従って、テレポーター・暗闇・回転床・落とし穴の順に置く
+    pub fn place_special_cells( &mut self, difficulty: &stage::Difficulty )
+    {   for _ in 0..difficulty.teleports  { self.place_teleporter() }
+        for _ in 0..difficulty.dark_zones { self.place_darkzone() }
+
+        //回転床は分岐点に置く（どちらから来たか分からなくなる）
+        for _ in 0..difficulty.spinners
+        {   let Some ( cell ) = self.pick_plain_floor( | map, cell | map.get_sides_space( cell ).len() >= 3 ) else { break };
+            self.add_flag_spinner( cell );
+        }
+
+        //落とし穴は通路に置く
+        for _ in 0..difficulty.pits
+        {   let Some ( cell ) = self.pick_plain_floor( | map, cell | map.get_sides_space( cell ).len() == 2 ) else { break };
+            self.add_flag_pit( cell );
+        }
+    }
+
+    //イベント表の順に、置く場所の種類に合う何も置かれていない床へイベントを置く
+    pub fn place_events( &mut self, table: &cell_events::EventTable )
+    {   for event in &table.events
+        {   let cell = match event.place
+            {   cell_events::Place::Deadend  => self.pick_plain_floor( | map, cell | map.is_deadend( cell ) ),
+                cell_events::Place::Corridor => self.pick_plain_floor( | map, cell | map.get_sides_space( cell ).len() == 2 ),
+                cell_events::Place::Junction => self.pick_plain_floor( | map, cell | map.get_sides_space( cell ).len() >= 3 ),
+                cell_events::Place::BeforeGoal =>
+                {   //ゴールは袋小路なので、開いている一方がゴールの一つ手前
+                    let sides = self.get_sides_space( self.goal );
+                    sides.first().map( | &news | self.goal + news ).filter( | &cell | self.is_plain_floor( cell ) )
+                }
+            };
+            let Some ( cell ) = cell else { continue };
+
+            self.put_event( cell, event.clone() );
+        }
+    }
+
+    //マスにイベントを置く（宝箱のように物を置くなら、進入禁止にして物のフラグを付加する）
+    pub fn put_event( &mut self, cell: IVec2, event: cell_events::EventDef )
+    {   self.add_flag_event( cell );
+        if let Some ( cell_events::Object::Chest ) = event.object
+        {   self.add_flag_noentry( cell );
+            self.add_flag_lockedchest( cell );
+        }
+        self.events.insert( cell, event );
+    }
+
+    //何も置かれていない床か？（スタート付近とテレポーターの飛ばし先も除く）
+    fn is_plain_floor( &self, cell: IVec2 ) -> bool
+    {   if ! self.is_space( cell ) || self.is_noentry( cell ) { return false }
+        if cell == self.start || cell == self.goal { return false }
+        if self.matrix( cell ).0 & ! MASK_INTERNAL_FLAGS != 0 { return false }
+        if self.is_event( cell ) { return false }
+        if self.teleports.values().any( | &dest | dest == cell ) { return false }
+
+        let distance = ( cell - self.start ).abs();
+        distance.x + distance.y >= SAFE_RADIUS_FROM_START
+    }
+
+    //条件に合う何も置かれていない床をランダムに一つ選ぶ
+    fn pick_plain_floor( &mut self, filter: impl Fn( &Self, IVec2 ) -> bool ) -> Option<IVec2>
+    {   let candidates: Vec<_> = self.x_range()
+            .flat_map( | x | self.y_range().map( move | y | IVec2::new( x, y ) ) )
+            .filter( | &cell | self.is_plain_floor( cell ) && filter( self, cell ) )
+            .collect();
+        if candidates.is_empty() { return None }
+
+        Some ( candidates[ self.rng.gen_range( 0..candidates.len() ) ] )
+    }
+
+    //テレポーターを置く（通り道をふさがないよう袋小路に限る。空いた袋小路がなければ置かない）
+    //飛ばし先は扉を通らずに歩いて行けるマスに限る（鍵を取らずに扉を越えられないように）
+    fn place_teleporter( &mut self )
+    {   let Some ( from ) = self.pick_plain_floor( | map, cell | map.is_deadend( cell ) ) else { return };
+
+        let mut dests: Vec<_> = self.reachable_cells( from ).into_iter()
+            .filter( | &cell | self.is_plain_floor( cell ) && cell != from )
+            .filter( | &cell | { let d = ( cell - from ).abs(); d.x + d.y >= TELEPORT_MIN_DISTANCE } )
+            .collect();
+        if dests.is_empty() { return }
+        dests.sort_by_key( | cell | ( cell.x, cell.y ) ); //HashSetの順序に依らないように
+
+        let dest = dests[ self.rng.gen_range( 0..dests.len() ) ];
+        self.add_flag_teleporter( from );
+        self.teleports.insert( from, dest );
+    }
+
+    //暗闇を置く（中心から一定の範囲の何も置かれていない床）
+    fn place_darkzone( &mut self )
+    {   let Some ( center ) = self.pick_plain_floor( | _, _ | true ) else { return };
+
+        let radius = DARKZONE_RADIUS;
+        for x in -radius..=radius
+        {   for y in -radius..=radius
+            {   let cell = center + IVec2::new( x, y );
+                if self.is_plain_floor( cell ) { self.add_flag_darkzone( cell ) }
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -0,0 +1,350 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//メッセージウィンドウの設定
+const MESSAGE_LINES_PER_PAGE: usize = 3;    //１ページの行数
+const MESSAGE_CHARS_PER_SEC : f32   = 40.0; //１秒間に表示する文字数
+const KEY_MESSAGE_NEXT: KeyCode = KeyCode::Return; //早送り・ページ送り
+const KEY_MESSAGE_CHOICES: [ KeyCode; 9 ] = //番号で選択肢を選ぶ
+[   KeyCode::Key1, KeyCode::Key2, KeyCode::Key3,
+    KeyCode::Key4, KeyCode::Key5, KeyCode::Key6,
+    KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+];
+const MESSAGE_WINDOW_HEIGHT      : f32   = PIXELS_PER_GRID * 5.0;
+const COLOR_MESSAGE_WINDOW_BG    : Color = Color::rgba( 0.0, 0.0, 0.2, 0.85 );
+const COLOR_MESSAGE_WINDOW_BORDER: Color = Color::SILVER;
+const FONT_SIZE_MESSAGE_TEXT     : f32   = PIXELS_PER_GRID * 0.5;
+const COLOR_MESSAGE_TEXT         : Color = Color::SILVER;
+const FONT_SIZE_MESSAGE_CHOICE   : f32   = PIXELS_PER_GRID * 0.5;
+const COLOR_MESSAGE_CHOICE       : Color = Color::SILVER;
+const MESSAGE_CUE_BLINK_SECS     : f32   = 0.5; //ページ送りの目印の点滅間隔
+const MESSAGE_PLAYBACK_PAGE_SECS : f32   = 1.0; //リプレイの再生中にページを自動で送るまでの時間
+
+counted_array!
+(   const TEXT_MESSAGE_CUE: [ init_app::MessageSect; _ ] =
+    [   ( "ENTER", ASSETS_FONT_PRESSSTART2P_REGULAR, PIXELS_PER_GRID * 0.4, Color::YELLOW ),
+    ]
+);
+
+//会話（本文は複数ページに分けて表示し、選択肢があれば最後に選ばせる）
+#[derive( Clone, Default )]
+pub struct Dialogue
+{   pub lines  : Vec<String>,              //本文
+    pub choices: Vec<cell_events::Choice>, //選択肢（空なら選ばずに閉じる）
+}
+
+impl Dialogue
+{   //ページ数
+    fn pages( &self ) -> usize
+    {   self.lines.chunks( MESSAGE_LINES_PER_PAGE ).count().max( 1 )
+    }
+
+    //ページの本文
+    fn page( &self, page: usize ) -> &[ String ]
+    {   self.lines.chunks( MESSAGE_LINES_PER_PAGE ).nth( page ).unwrap_or( &[] )
+    }
+}
+
+//表示待ちの会話のResource
+#[derive( Resource, Default )]
+pub struct MessageQueue ( VecDeque<Dialogue> );
+
+impl MessageQueue
+{   //本文だけの会話を表示待ちにする
+    pub fn push<S: ToString>( &mut self, lines: &[ S ] )
+    {   let lines = lines.iter().map( ToString::to_string ).collect();
+        self.0.push_back( Dialogue { lines, choices: Vec::new() } );
+    }
+
+    //選択肢のある会話を表示待ちにする
+    pub fn ask<S: ToString>( &mut self, lines: &[ S ], choices: &[ cell_events::Choice ] )
+    {   let lines = lines.iter().map( ToString::to_string ).collect();
+        self.0.push_back( Dialogue { lines, choices: choices.to_vec() } );
+    }
+
+    pub fn is_empty( &self ) -> bool { self.0.is_empty() }
+}
+
+//メッセージウィンドウのComponent（表示中のページと表示済みの文字数）
+#[derive( Component, Default )]
+pub struct MessageWindow
+{   page : usize,
+    typed: f32,
+}
+
+//メッセージウィンドウの本文のComponent（一行が一つのTextSectionになる）
+#[derive( Component )]
+pub struct MessageText;
+
+//ページ送りの目印のComponent
+#[derive( Component )]
+pub struct MessageCue;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ゲーム開始時に表示待ちの会話を捨てる
+pub fn reset_queue( mut queue: ResMut<MessageQueue> )
+{   queue.0.clear();
+}
+
+//表示待ちの会話があればメッセージウィンドウを開く
+//（他の遷移が決まっていれば、遷移先から戻った後で表示する）
+pub fn open_window
+(   queue: Res<MessageQueue>,
+    mut next_state: ResMut<NextState<MyState>>,
+)
+{   if queue.is_empty() || next_state.0.is_some() { return }
+
+    next_state.set( MyState::Message );
+}
+
+//メッセージウィンドウをspawnする
+pub fn spawn_window
+(   mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+)
+{   spawn_message_window( &mut cmds, &asset_svr );
+}
+
+//3D viewportの下端にメッセージウィンドウをspawnする（本文は空で、type_text()が一文字ずつ表示する）
+fn spawn_message_window( cmds: &mut Commands, asset_svr: &Res<AssetServer> )
+{   let ViewPortInfo { origin, size } = &SCREEN_FRAME.viewport;
+    let margin = PIXELS_PER_GRID * 0.5;
+    let style = Style
+    {   position_type  : PositionType::Absolute,
+        left           : Val::Px( origin.x + margin ),
+        top            : Val::Px( origin.y + size.y - MESSAGE_WINDOW_HEIGHT - margin ),
+        width          : Val::Px( size.x - margin * 2.0 ),
+        height         : Val::Px( MESSAGE_WINDOW_HEIGHT ),
+        flex_direction : FlexDirection::Row,
+        justify_content: JustifyContent::SpaceBetween,
+        align_items    : AlignItems::FlexStart,
+        padding        : UiRect::all( Val::Px( margin ) ),
+        border         : UiRect::all( Val::Px( 2.0 ) ),
+        ..default()
+    };
+    let background_color = BackgroundColor ( COLOR_MESSAGE_WINDOW_BG );
+    let border_color = BorderColor ( COLOR_MESSAGE_WINDOW_BORDER );
+    let window = NodeBundle { style, background_color, border_color, ..default() };
+
+    //本文（１ページの行数分のTextSectionを用意しておく）
+    let sections: Vec<init_app::MessageSect> = ( 0..MESSAGE_LINES_PER_PAGE )
+        .map( | _ | ( "", ASSETS_FONT_PRESSSTART2P_REGULAR, FONT_SIZE_MESSAGE_TEXT, COLOR_MESSAGE_TEXT ) )
+        .collect();
+    let mut text = init_app::text_ui( &sections, asset_svr );
+    text.style.position_type = PositionType::Relative;
+
+    //ページ送りの目印（右下）
+    let mut cue = init_app::text_ui( &TEXT_MESSAGE_CUE, asset_svr );
+    cue.style.position_type = PositionType::Absolute;
+    cue.style.right  = Val::Px( margin );
+    cue.style.bottom = Val::Px( margin );
+    cue.visibility = Visibility::Hidden;
+
+    cmds.spawn( ( window, MessageWindow::default() ) )
+    .with_children
+    (   | cmds |
+        {   cmds.spawn( ( text, MessageText ) );
+            cmds.spawn( ( cue, MessageCue ) );
+        }
+    );
+}
+
+//選択肢をメッセージウィンドウの右側にspawnする（カーソル移動と決定はmenuのsystemが行う）
+fn spawn_choices
+(   cmds: &mut Commands,
+    window: Entity,
+    asset_svr: &Res<AssetServer>,
+    choices: &[ cell_events::Choice ],
+)
+{   let items: Vec<_> = ( 0..choices.len() ).map( menu::MenuItem::Choice ).collect();
+    let labels: Vec<_> = choices.iter().enumerate()
+        .map( | ( i, choice ) | format!( "{}.{}", i + 1, choice.label ) )
+        .collect();
+
+    let lines: Vec<_> = labels.iter().map( | label | format!( "{label}\n" ) ).collect();
+    let sections: Vec<init_app::MessageSect> = lines.iter()
+        .map( | line | ( line.as_str(), ASSETS_FONT_PRESSSTART2P_REGULAR, FONT_SIZE_MESSAGE_CHOICE, COLOR_MESSAGE_CHOICE ) )
+        .collect();
+    let mut text = init_app::text_ui( &sections, asset_svr );
+    text.style.position_type = PositionType::Relative;
+
+    let menu = menu::Menu::with_labels( &items, labels, &[] );
+    let id = cmds.spawn( ( text, menu ) ).id();
+    cmds.entity( window ).add_child( id );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//[Enter]キーで、表示途中なら早送り、表示し終えていれば次のページへ送る
+//選択肢がなければ最後のページで閉じる（選択肢はselect_choice()で選ぶ）
+//（リプレイの再生中は、表示し終えて一定時間たったら自動でページを送る）
+#[allow(clippy::too_many_arguments)]
+pub fn turn_page
+(   mut qry_window: Query<( Entity, &mut MessageWindow )>,
+    mut queue: ResMut<MessageQueue>,
+    mut next_state: ResMut<NextState<MyState>>,
+    mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+    inkey: Res<Input<KeyCode>>,
+    opt_playback: Option<Res<replay::Playback>>,
+    time: Res<Time>,
+    mut wait: Local<f32>,
+)
+{   let Ok ( ( id, mut window ) ) = qry_window.get_single_mut() else { return };
+    let Some ( dialogue ) = queue.0.front() else { return };
+    let total = page_chars( dialogue.page( window.page ) );
+    let is_typed = window.typed >= total as f32;
+
+    //再生中は表示し終えてからの時間を計る
+    let is_auto = opt_playback.is_some() && is_typed &&
+    {   *wait += time.delta_seconds();
+        *wait >= MESSAGE_PLAYBACK_PAGE_SECS
+    };
+    let is_alt_pressed = inkey.any_pressed( [ KeyCode::AltLeft, KeyCode::AltRight ] );
+    let is_pressed = inkey.just_pressed( KEY_MESSAGE_NEXT ) && ! is_alt_pressed;
+    if ! is_auto && ! is_pressed { return }
+    *wait = 0.0;
+
+    //表示途中なら早送りする
+    if ! is_typed { window.typed = total as f32; return }
+
+    //次のページへ
+    if window.page + 1 < dialogue.pages() { *window = MessageWindow { page: window.page + 1, typed: 0.0 }; return }
+
+    //選択肢があれば閉じない
+    if ! dialogue.choices.is_empty() { return }
+
+    //会話を閉じて、次の会話があればウィンドウを作り直す
+    queue.0.pop_front();
+    cmds.entity( id ).despawn_recursive();
+    if queue.is_empty()
+    {   next_state.set( MyState::MainLoop );
+    }
+    else
+    {   spawn_message_window( &mut cmds, &asset_svr );
+    }
+}
+
+//選択肢が決まったら、その中身を知らせて迷路に戻る（[1]～[9]キーでも選べる）
+//（中身はMainLoopのhandlerが実行するので、残りの会話は戻ってから開き直す）
+//（選んだ番号はリプレイに記録し、再生中は記録された番号を選ぶ）
+#[allow(clippy::too_many_arguments)]
+pub fn select_choice
+(   mut evt_selected: EventReader<menu::MenuSelected>,
+    mut evt_action: EventWriter<cell_events::RunAction>,
+    qry_menu: Query<&menu::Menu>,
+    mut queue: ResMut<MessageQueue>,
+    mut next_state: ResMut<NextState<MyState>>,
+    mut recorder: ResMut<replay::Recorder>,
+    mut opt_playback: Option<ResMut<replay::Playback>>,
+    stage: Res<stage::Stage>,
+    record: Res<hud::PlayRecord>,
+    time: Res<Time>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   let Ok ( menu ) = qry_menu.get_single() else { return };
+
+    //再生中は数字キーを受け付けない
+    let is_replaying = opt_playback.as_ref().is_some_and( | playback | playback.is_selecting( stage.number ) );
+    let playback = opt_playback.as_deref_mut();
+    let by_menu = replay::select_item( &mut evt_selected, playback, stage.number, &time ).and_then
+    (   | item |
+        if let menu::MenuItem::Choice ( index ) = item { Some ( index ) } else { None }
+    );
+    let by_key = KEY_MESSAGE_CHOICES.iter()
+        .take( menu.items.len() )
+        .position( | &key | inkey.just_pressed( key ) )
+        .filter( | _ | ! is_replaying );
+    let Some ( index ) = by_menu.or( by_key ) else { return };
+    let item = menu::MenuItem::Choice ( index );
+    recorder.record( stage.number, record.stage_elapsed, replay::PlayerCommand::Select ( item ) );
+
+    let Some ( dialogue ) = queue.0.pop_front() else { return };
+    let Some ( choice ) = dialogue.choices.get( index ) else { return };
+    evt_action.send_batch( choice.actions.iter().cloned().map( cell_events::RunAction ) );
+    next_state.set( MyState::MainLoop );
+}
+
+//本文を一文字ずつ表示し、表示し終えたらページ送りの目印か選択肢を出す
+#[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments)]
+pub fn type_text
+(   mut qry_window: Query<( Entity, &mut MessageWindow )>,
+    mut qry_text: Query<&mut Text, With<MessageText>>,
+    mut qry_cue: Query<&mut Visibility, With<MessageCue>>,
+    qry_menu: Query<(), With<menu::Menu>>,
+    queue: Res<MessageQueue>,
+    mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+    time: Res<Time>,
+)
+{   let Ok ( ( id, mut window ) ) = qry_window.get_single_mut() else { return };
+    let Ok ( mut text ) = qry_text.get_single_mut() else { return };
+    let Ok ( mut cue ) = qry_cue.get_single_mut() else { return };
+    let Some ( dialogue ) = queue.0.front() else { return };
+
+    //表示する文字数を増やす
+    let lines = dialogue.page( window.page );
+    let total = page_chars( lines );
+    window.typed = ( window.typed + time.delta_seconds() * MESSAGE_CHARS_PER_SEC ).min( total as f32 );
+
+    //先頭の行から表示済みの文字数だけ表示する
+    let mut rest = window.typed as usize;
+    for ( i, section ) in text.sections.iter_mut().enumerate()
+    {   let line = lines.get( i ).map( String::as_str ).unwrap_or( "" );
+        section.value = line.chars().take( rest ).collect::<String>() + "\n";
+        rest = rest.saturating_sub( line.chars().count() );
+    }
+
+    //表示し終えたら、最後のページに選択肢があれば出し、なければ目印を点滅させる
+    let is_typed = window.typed as usize >= total;
+    let is_last = window.page + 1 >= dialogue.pages();
+    let has_choices = is_last && ! dialogue.choices.is_empty();
+    if is_typed && has_choices && qry_menu.is_empty()
+    {   spawn_choices( &mut cmds, id, &asset_svr, &dialogue.choices );
+    }
+
+    let is_blink_on = ( time.elapsed_seconds() / ( MESSAGE_CUE_BLINK_SECS * 2.0 ) ).fract() < 0.5;
+    *cue = if is_typed && ! has_choices && is_blink_on { Visibility::Inherited } else { Visibility::Hidden };
+}
+
+//ページの文字数
+fn page_chars( lines: &[ String ] ) -> usize
+{   lines.iter().map( | line | line.chars().count() ).sum()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests
+{   use super::*;
+
+    fn dialogue( count: usize ) -> Dialogue
+    {   let lines = ( 0..count ).map( | i | format!( "LINE {i}" ) ).collect();
+        Dialogue { lines, choices: Vec::new() }
+    }
+
+    //本文がなくても１ページ、決まった行数ごとにページが分かれる
+    #[test]
+    fn dialogue_pages()
+    {   assert_eq!( dialogue( 0 ).pages(), 1 );
+        assert_eq!( dialogue( 1 ).pages(), 1 );
+        assert_eq!( dialogue( MESSAGE_LINES_PER_PAGE ).pages(), 1 );
+        assert_eq!( dialogue( MESSAGE_LINES_PER_PAGE + 1 ).pages(), 2 );
+    }
+
+    //最後のページは残りの行だけ、範囲外のページは空
+    #[test]
+    fn dialogue_page_lines()
+    {   let dialogue = dialogue( MESSAGE_LINES_PER_PAGE + 1 );
+        assert_eq!( dialogue.page( 0 ).len(), MESSAGE_LINES_PER_PAGE );
+        assert_eq!( dialogue.page( 1 ), &[ format!( "LINE {MESSAGE_LINES_PER_PAGE}" ) ] );
+        assert!( dialogue.page( 2 ).is_empty() );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
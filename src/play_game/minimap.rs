@@ -2,9 +2,9 @@ use super::*;
 
 ////////////////////////////////////////////////////////////////////////////////
 
-//ミニマップの各セルのComponent
-#[derive( Component, Debug )]
-pub struct MiniMapCell ( pub IVec2 );
+//ミニマップのテクスチャのResource（１マスを１ピクセルで描く）
+#[derive( Resource )]
+pub struct MinimapImage ( Handle<Image> );
 
 //ミニマップ上の三角形のComponent
 #[derive( Component )]
@@ -14,9 +14,97 @@ pub struct MinimapPlayer;
 #[derive( Component )]
 pub struct MinimapCamera;
 
+//ミニマップの霧（未踏査のマスを隠す）のResource
+#[derive( Resource, Default )]
+pub struct MinimapFog { pub is_disabled: bool }
+
+//霧の切替キー（debug用）
+const KEY_TOGGLE_FOG: KeyCode = KeyCode::F1;
+
+//ミニマップの向きのResource
+#[derive( Resource, Default, Clone, Copy, PartialEq, Eq )]
+pub enum MinimapOrientation
+{   #[default] NorthUp, //北が常に上
+    HeadingUp,          //プレイヤーの向きが常に上
+}
+
+//向きの切替キー
+const KEY_TOGGLE_ORIENTATION: KeyCode = KeyCode::R;
+
+//ミニマップの拡大率のResource（ZOOM_LEVELSの添字）
+#[derive( Resource )]
+pub struct MinimapZoom ( usize );
+
+impl Default for MinimapZoom
+{   fn default() -> Self { Self ( ZOOM_LEVEL_DEFAULT ) }
+}
+
+//2Dカメラのprojection.scale（小さいほど拡大）
+const ZOOM_LEVELS: [ f32; 4 ] = [ 0.5, 1.0, 1.5, 2.5 ];
+const ZOOM_LEVEL_DEFAULT: usize = 1;
+
+//拡大縮小のキー
+const KEYS_ZOOM_IN : [ KeyCode; 2 ] = [ KeyCode::Equals, KeyCode::NumpadAdd      ];
+const KEYS_ZOOM_OUT: [ KeyCode; 2 ] = [ KeyCode::Minus , KeyCode::NumpadSubtract ];
+
+//ミニマップのマーカーのComponent
+#[derive( Component )]
+pub struct MinimapMarker ( IVec2, u128 ); //マスの座標、対象のフラグ
+
+//マーカーの見た目
+struct MarkerStyle
+{   bit  : u128,  //対象のフラグ
+    color: Color, //色
+    sides: usize, //正多角形の頂点数
+}
+
+counted_array!
+(   const MARKER_STYLES: [ MarkerStyle; _ ] =
+    [   MarkerStyle { bit: map::BIT_FLAG_LOCKEDCHEST, color: Color::ORANGE    , sides: 4 },
+        MarkerStyle { bit: map::BIT_FLAG_OPENEDCHEST, color: Color::GRAY      , sides: 4 },
+        MarkerStyle { bit: map::BIT_FLAG_GOAL       , color: Color::CYAN      , sides: 8 },
+        MarkerStyle { bit: map::BIT_FLAG_STAIRS     , color: Color::LIME_GREEN, sides: 6 },
+        MarkerStyle { bit: map::BIT_FLAG_LOCKEDDOOR , color: Color::GOLD      , sides: 5 },
+        MarkerStyle { bit: map::BIT_FLAG_OPENEDDOOR , color: Color::DARK_GRAY , sides: 5 },
+        MarkerStyle { bit: map::BIT_FLAG_PIT        , color: Color::PURPLE    , sides: 4 },
+        MarkerStyle { bit: map::BIT_FLAG_TELEPORTER , color: Color::FUCHSIA   , sides: 6 },
+        MarkerStyle { bit: map::BIT_FLAG_SPINNER    , color: Color::PINK      , sides: 7 },
+        MarkerStyle { bit: map::BIT_FLAG_DARKZONE   , color: Color::NAVY      , sides: 4 },
+    ]
+);
+
+//表に無いフラグのマーカー（新しいマップの要素も、とりあえずミニマップに表示される）
+const MARKER_STYLE_DEFAULT: MarkerStyle = MarkerStyle { bit: 0, color: Color::WHITE, sides: 5 };
+const RADIUS_SPRITE_MINIMAP_MARKER: f32 = PIXELS_PER_GRID * 0.35;
+
+//フラグ毎のマーカーのメッシュとマテリアルのResource
+#[derive( Resource, Default )]
+pub struct MarkerHandles ( HashMap<u128, ( Handle<Mesh>, Handle<ColorMaterial> )> );
+
+//ミニマップの足跡のComponent
+#[derive( Component )]
+pub struct Breadcrumb ( Timer );
+
+//足跡の設定
+const COLOR_SPRITE_BREADCRUMB: Color = Color::YELLOW;
+const SIZE_SPRITE_BREADCRUMB : Vec2 = Vec2::new( PIXELS_PER_GRID * 0.2, PIXELS_PER_GRID * 0.2 );
+const ALPHA_SPRITE_BREADCRUMB: f32 = 0.8; //置いた直後の透明度
+const SECS_BREADCRUMB_FADE   : f32 = 20.0; //消えるまでの秒数
+
+//ミニマップの壁にぶつかった印のComponent
+#[derive( Component )]
+pub struct BumpFlash ( Timer );
+
+//壁にぶつかった印の設定
+const COLOR_SPRITE_BUMP_FLASH: Color = Color::RED;
+const SIZE_SPRITE_BUMP_FLASH : Vec2 = Vec2::new( PIXELS_PER_GRID * 0.8, PIXELS_PER_GRID * 0.8 );
+const ALPHA_SPRITE_BUMP_FLASH: f32 = 0.7; //置いた直後の透明度
+const SECS_BUMP_FLASH_FADE   : f32 = 0.4; //消えるまでの秒数
+
 //ミニマップのスプライトの情報
 const COLOR_SPRITE_MINIMAP_CELL_BASE: Color = GROUND_PLANE_COLOR;
 const COLOR_SPRITE_MINIMAP_CELL_WALL: Color = WALL_CUBE_COLOR;
+const COLOR_SPRITE_MINIMAP_CELL_FOG : Color = Color::rgb( 0.05, 0.05, 0.08 );
 const COLOR_SPRITE_MINIMAP_PLAYER   : Color = Color::YELLOW;
 const RADIUS_SPRITE_MINIMAP_PLAYER  : f32 = PIXELS_PER_GRID * 0.3; //正多角形の外接円の半径
 
@@ -27,47 +115,31 @@ pub fn spawn_minimap
 (   mut cmds: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials_color: ResMut<Assets<ColorMaterial>>,
-    asset_svr: Res<AssetServer>, //for debug
+    mut images: ResMut<Assets<Image>>,
 )
-{   //ミニマップにスプライトを敷き詰める
-    let scale = if misc::DEBUG() { 0.9 } else { 1.0 };
-    let custom_size = Some ( SIZE_GRID * scale );
-    let color = COLOR_SPRITE_MINIMAP_CELL_BASE;
-    let x_grids_range = 0..MAP_GRIDS_WIDTH  + SCREEN_FRAME.minimap.size.x;
-    let y_grids_range = 0..MAP_GRIDS_HEIGHT + SCREEN_FRAME.minimap.size.y;
-    let x_grids_adjuster = IVec2::X * SCREEN_GRIDS_WIDTH; //X軸で１画面分ずらす
-
-    for y in y_grids_range
-    {   for x in x_grids_range.clone()
-        {   //スプライトをspawn
-            let grid = IVec2::new( x, y );
-            let work = grid + x_grids_adjuster;
-            let translation = work.to_screen_pixel().extend( DEPTH_SPRITE_MINIMAP );
-            cmds.spawn( ( SpriteBundle::default(), MiniMapCell ( grid ) ) )
-            .insert( Sprite { custom_size, color, ..default() } )
-            .insert( Transform::from_translation( translation ) )
-            .with_children //for debug
-            (   | cmds |
-                {   if misc::DEBUG()
-                    {   //debug用文字列
-                        let value = format!( "{:02}\n{:02}", x, y ).to_string();
-                        let style = TextStyle
-                        {   font     : asset_svr.load( ASSETS_FONT_PRESSSTART2P_REGULAR ),
-                            font_size: PIXELS_PER_GRID * 0.25,
-                            color    : Color::DARK_GREEN,
-                        };
-                        let sections  = vec![ TextSection { value, style } ];
-                        let alignment = TextAlignment::Center;
-
-                        cmds.spawn( Text2dBundle::default() )
-                        .insert( Text { sections, alignment, ..default() } )
-                        .insert( Transform::from_translation( Vec3::Z ) )
-                        ;
-                    }
-                }
-            );
-        }
-    }
+{   //ミニマップのテクスチャを作る（マップの周囲にミニマップ半分の余白を持たせる）
+    let grids = IVec2::new( MAP_GRIDS_WIDTH, MAP_GRIDS_HEIGHT ) + SCREEN_FRAME.minimap.size;
+    let size = Extent3d
+    {   width : grids.x as u32,
+        height: grids.y as u32,
+        depth_or_array_layers: 1,
+    };
+    let pixel = COLOR_SPRITE_MINIMAP_CELL_FOG.as_rgba_u8();
+    let image = Image::new_fill( size, TextureDimension::D2, &pixel, TextureFormat::Rgba8UnormSrgb );
+    let handle = images.add( image );
+
+    //テクスチャを１枚のスプライトとして表示する
+    //（左上隅をX軸で１画面分ずらした位置に合わせる）
+    let custom_size = Some ( grids.as_vec2() * PIXELS_PER_GRID );
+    let anchor = Anchor::TopLeft;
+    let translation = ( Vec2::X * SCREEN_PIXELS_WIDTH ).extend( DEPTH_SPRITE_MINIMAP );
+
+    cmds.spawn( SpriteBundle::default() )
+    .insert( Sprite { custom_size, anchor, ..default() } )
+    .insert( Transform::from_translation( translation ) )
+    .insert( handle.clone() )
+    ;
+    cmds.insert_resource( MinimapImage ( handle ) );
 
     //プレイヤーの三角形をspawnする
     let radius = RADIUS_SPRITE_MINIMAP_PLAYER;
@@ -77,12 +149,7 @@ pub fn spawn_minimap
         visibility: Visibility::Hidden, //ちらっと見えるので無効化しておく
         ..default()
     };
-    let grid = SCREEN_FRAME.minimap.zero + SCREEN_FRAME.minimap.size / 2;
-    let translation = grid.to_screen_pixel().extend( DEPTH_SPRITE_MINIMAP_PLAYER );
-
-    cmds.spawn( ( triangle, MinimapPlayer ) )
-    .insert( Transform::from_translation( translation ) )
-    ;
+    cmds.spawn( ( triangle, MinimapPlayer ) ); //位置はsetup_minimap()でセットする
 
     //ミニマップ用2Dカメラをspawnする
     let zero = SCREEN_FRAME.minimap.zero.as_vec2() * PIXELS_PER_GRID;
@@ -107,42 +174,30 @@ pub fn spawn_minimap
 //ミニマップの初期表示
 #[allow(clippy::type_complexity)]
 pub fn setup_minimap
-(   mut qry_minimap_sprite: Query<( &mut Sprite, &MiniMapCell )>,
-    mut param_set: ParamSet
+(   mut param_set: ParamSet
     <(  Query<( &mut Transform, &mut Visibility ), With<MinimapPlayer> >,
         Query<( &mut Transform, &mut Camera ), With<MinimapCamera> >,
     )>,
     opt_map: Option<Res<map::Map>>,
     opt_player: Option<Res<player::Player>>,
+    fog: Res<MinimapFog>,
+    minimap_image: Res<MinimapImage>,
+    mut images: ResMut<Assets<Image>>,
 )
 {   let Some ( map ) = opt_map else { return };
     let Some ( player ) = opt_player else { return };
 
-    //ミニマップのスプライト更新の準備
-    let mut sprite_hash = HashMap::new();
-    qry_minimap_sprite.for_each_mut( | ( s, m ) | { sprite_hash.insert( m.0, s ); } );
-    let x_range = 0..MAP_GRIDS_WIDTH  + SCREEN_FRAME.minimap.size.x;
-    let y_range = 0..MAP_GRIDS_HEIGHT + SCREEN_FRAME.minimap.size.y;
-    let adjuster = SCREEN_FRAME.minimap.size / 2; //奇数／２の場合の端数は切り捨て
-
-    //ミニマップのスプライトの表示を更新する
-    for y in y_range
-    {   for x in x_range.clone()
-        {   let grid = IVec2::new( x, y );
-            let Some ( sprite ) = sprite_hash.get_mut( &grid ) else { continue };
-
-            //スプライトの色を変更
-            sprite.color = match map.is_wall( grid - adjuster )
-            {   true  => COLOR_SPRITE_MINIMAP_CELL_WALL,
-                false => COLOR_SPRITE_MINIMAP_CELL_BASE,
-            };
-        }
+    //ミニマップのテクスチャを全面塗り直す
+    if let Some ( image ) = images.get_mut( &minimap_image.0 )
+    {   paint_all_cells( image, &map, &fog );
     }
 
-    //プレイヤーの三角形の向きをセットする
+    //プレイヤーの三角形の位置と向きをセットする
+    //（三角形はミニマップ側の2D空間にあり、カメラと一緒に移動する）
     let mut qry_player = param_set.p0();
     if let Ok ( ( mut transform, mut visibility ) ) = qry_player.get_single_mut()
-    {   transform.rotation = player.direction.to_quat_z();
+    {   transform.translation = map.start.to_minimap_center().truncate().extend( DEPTH_SPRITE_MINIMAP_PLAYER );
+        transform.rotation = player.direction.to_quat_z();
         *visibility = Visibility::Visible;
     }
 
@@ -156,6 +211,297 @@ pub fn setup_minimap
 
 ////////////////////////////////////////////////////////////////////////////////
 
+//マスの状態に応じたミニマップの色を返す
+fn cell_color( map: &map::Map, fog: &MinimapFog, cell: IVec2 ) -> Color
+{   if ! fog.is_disabled && ! map.is_explored( cell )
+    {   COLOR_SPRITE_MINIMAP_CELL_FOG
+    }
+    else if map.is_wall( cell )
+    {   COLOR_SPRITE_MINIMAP_CELL_WALL
+    }
+    else
+    {   COLOR_SPRITE_MINIMAP_CELL_BASE
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//テクスチャの１ピクセル（＝１マス）を塗る
+fn paint_cell( image: &mut Image, map: &map::Map, fog: &MinimapFog, cell: IVec2 )
+{   let adjuster = SCREEN_FRAME.minimap.size / 2; //奇数／２の場合の端数は切り捨て
+    let pixel = cell + adjuster;
+    let width  = image.texture_descriptor.size.width  as i32;
+    let height = image.texture_descriptor.size.height as i32;
+    if ! ( 0..width ).contains( &pixel.x ) || ! ( 0..height ).contains( &pixel.y ) { return }
+
+    let index = ( pixel.y * width + pixel.x ) as usize * 4; //RGBAの4byte
+    let rgba = cell_color( map, fog, cell ).as_rgba_u8();
+    image.data[ index..index + 4 ].copy_from_slice( &rgba );
+}
+
+//テクスチャ全体を塗る
+fn paint_all_cells( image: &mut Image, map: &map::Map, fog: &MinimapFog )
+{   let adjuster = SCREEN_FRAME.minimap.size / 2;
+    let width  = image.texture_descriptor.size.width  as i32;
+    let height = image.texture_descriptor.size.height as i32;
+
+    for y in 0..height
+    {   for x in 0..width
+        {   paint_cell( image, map, fog, IVec2::new( x, y ) - adjuster );
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//プレイヤーから見通せるマスを踏査済みにする
+pub fn reveal_around_player
+(   mut map: ResMut<map::Map>,
+    player: Res<player::Player>,
+)
+{   if ! player.is_changed() { return }
+
+    map.explore_sight( player.position );
+}
+
+//状態が変わったマスだけテクスチャを塗り直す
+pub fn repaint_changed_cells
+(   mut evt_changed: EventReader<map::CellChanged>,
+    map: Res<map::Map>,
+    fog: Res<MinimapFog>,
+    minimap_image: Res<MinimapImage>,
+    mut images: ResMut<Assets<Image>>,
+)
+{   if evt_changed.is_empty() { return }
+    let Some ( image ) = images.get_mut( &minimap_image.0 ) else { return };
+
+    for map::CellChanged ( cell ) in evt_changed.iter()
+    {   paint_cell( image, &map, &fog, *cell );
+    }
+}
+
+//debug用：ミニマップの霧を切り替える(トグル動作)
+pub fn toggle_fog
+(   mut fog: ResMut<MinimapFog>,
+    map: Res<map::Map>,
+    minimap_image: Res<MinimapImage>,
+    mut images: ResMut<Assets<Image>>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   if ! inkey.just_pressed( KEY_TOGGLE_FOG ) { return }
+
+    //霧を切り替えて、テクスチャを全面塗り直す
+    fog.is_disabled = ! fog.is_disabled;
+    let Some ( image ) = images.get_mut( &minimap_image.0 ) else { return };
+    paint_all_cells( image, &map, &fog );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//マーカーを表示するか？（踏査済みのマスだけ表示する）
+fn is_marker_visible( map: &map::Map, fog: &MinimapFog, cell: IVec2 ) -> Visibility
+{   if fog.is_disabled || map.is_explored( cell ) { Visibility::Inherited } else { Visibility::Hidden }
+}
+
+//マスのフラグに応じたマーカーをspawnする
+#[allow(clippy::too_many_arguments)]
+fn spawn_cell_markers
+(   cmds: &mut Commands,
+    handles: &mut MarkerHandles,
+    meshes: &mut Assets<Mesh>,
+    materials_color: &mut Assets<ColorMaterial>,
+    map: &map::Map,
+    fog: &MinimapFog,
+    cell: IVec2,
+    bits: &[ u128 ],
+)
+{   for &bit in bits
+    {   //フラグに対応するメッシュとマテリアルを用意する（初回のみ作成）
+        let ( mesh, material ) = handles.0.entry( bit ).or_insert_with
+        (   ||
+            {   let style = MARKER_STYLES.iter().find( | style | style.bit == bit )
+                    .unwrap_or( &MARKER_STYLE_DEFAULT );
+                let polygon = shape::RegularPolygon::new( RADIUS_SPRITE_MINIMAP_MARKER, style.sides );
+                ( meshes.add( polygon.into() ), materials_color.add( ColorMaterial::from( style.color ) ) )
+            }
+        );
+
+        let marker = MaterialMesh2dBundle
+        {   mesh: mesh.clone().into(),
+            material: material.clone(),
+            visibility: is_marker_visible( map, fog, cell ),
+            ..default()
+        };
+        let translation = cell.to_minimap_center().truncate().extend( DEPTH_SPRITE_MINIMAP_MARKER );
+
+        cmds.spawn( ( marker, MinimapMarker ( cell, bit ) ) )
+        .insert( Transform::from_translation( translation ) )
+        ;
+    }
+}
+
+//ステージ開始時にマーカーをspawnする（足跡と壁にぶつかった印は消す）
+type WithMarkerOrBreadcrumb = Or<( With<MinimapMarker>, With<Breadcrumb>, With<BumpFlash> )>;
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_markers
+(   qry_marker: Query<Entity, WithMarkerOrBreadcrumb>,
+    mut handles: ResMut<MarkerHandles>,
+    map: Res<map::Map>,
+    fog: Res<MinimapFog>,
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials_color: ResMut<Assets<ColorMaterial>>,
+)
+{   //既存のマーカーと足跡と印があれば削除する
+    qry_marker.for_each( | id | cmds.entity( id ).despawn_recursive() );
+
+    for x in map.x_range()
+    {   for y in map.y_range()
+        {   let cell = IVec2::new( x, y );
+            let bits = map.feature_flags( cell );
+            spawn_cell_markers( &mut cmds, &mut handles, &mut meshes, &mut materials_color, &map, &fog, cell, &bits );
+        }
+    }
+}
+
+//状態が変わったマスのマーカーを更新する
+//（踏査済みになっただけなら表示を切り替えるだけ。フラグが増減した分だけspawn/despawnする）
+#[allow(clippy::too_many_arguments)]
+pub fn sync_markers
+(   mut qry_marker: Query<( Entity, &mut Visibility, &MinimapMarker )>,
+    mut evt_changed: EventReader<map::CellChanged>,
+    mut handles: ResMut<MarkerHandles>,
+    map: Res<map::Map>,
+    fog: Res<MinimapFog>,
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials_color: ResMut<Assets<ColorMaterial>>,
+)
+{   if evt_changed.is_empty() { return }
+
+    //変化のあったマスごとに、いま立っているフラグを調べる
+    let mut changed: HashMap<IVec2, Vec<u128>> = HashMap::new();
+    for map::CellChanged ( cell ) in evt_changed.iter()
+    {   changed.entry( *cell ).or_insert_with( || map.feature_flags( *cell ) );
+    }
+
+    //既存のマーカーは、フラグが消えていればdespawnし、残っていれば表示だけ更新する
+    for ( id, mut visibility, &MinimapMarker ( cell, bit ) ) in qry_marker.iter_mut()
+    {   let Some ( bits ) = changed.get_mut( &cell ) else { continue };
+
+        if let Some ( index ) = bits.iter().position( | &x | x == bit )
+        {   bits.swap_remove( index );
+            *visibility = is_marker_visible( &map, &fog, cell );
+        }
+        else
+        {   cmds.entity( id ).despawn_recursive();
+        }
+    }
+
+    //マーカーがまだないフラグだけspawnする
+    for ( cell, bits ) in changed
+    {   if bits.is_empty() { continue }
+        spawn_cell_markers( &mut cmds, &mut handles, &mut meshes, &mut materials_color, &map, &fog, cell, &bits );
+    }
+}
+
+//霧の切替に合わせてマーカーの表示を更新する
+pub fn update_marker_visibility
+(   mut qry_marker: Query<( &mut Visibility, &MinimapMarker )>,
+    map: Res<map::Map>,
+    fog: Res<MinimapFog>,
+)
+{   if ! fog.is_changed() { return }
+
+    qry_marker.for_each_mut
+    (   | ( mut visibility, marker ) |
+        *visibility = is_marker_visible( &map, &fog, marker.0 )
+    );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//プレイヤーが立ち去ったマスに足跡を置く
+pub fn drop_breadcrumb
+(   player: Res<player::Player>,
+    mut cmds: Commands,
+    mut last_position: Local<Option<IVec2>>,
+)
+{   let Some ( last ) = last_position.replace( player.position ) else { return };
+    if last == player.position { return }
+
+    //隣のマスへ移動した場合だけ置く（ステージ開始時などの瞬間移動は除く）
+    if ( player.position - last ).abs().max_element() != 1 { return }
+
+    let custom_size = Some ( SIZE_SPRITE_BREADCRUMB );
+    let color = COLOR_SPRITE_BREADCRUMB.with_a( ALPHA_SPRITE_BREADCRUMB );
+    let translation = last.to_minimap_center().truncate().extend( DEPTH_SPRITE_MINIMAP_TRAIL );
+    let timer = Timer::from_seconds( SECS_BREADCRUMB_FADE, TimerMode::Once );
+
+    cmds.spawn( ( SpriteBundle::default(), Breadcrumb ( timer ) ) )
+    .insert( Sprite { custom_size, color, ..default() } )
+    .insert( Transform::from_translation( translation ) )
+    ;
+}
+
+//足跡を徐々に薄くして、最後に削除する
+pub fn fade_breadcrumbs
+(   mut qry_breadcrumb: Query<( Entity, &mut Sprite, &mut Breadcrumb )>,
+    time: Res<Time>,
+    mut cmds: Commands,
+)
+{   for ( id, mut sprite, mut breadcrumb ) in qry_breadcrumb.iter_mut()
+    {   let timer = &mut breadcrumb.0;
+        if timer.tick( time.delta() ).finished()
+        {   cmds.entity( id ).despawn_recursive();
+            continue;
+        }
+
+        let alpha = ALPHA_SPRITE_BREADCRUMB * timer.percent_left();
+        sprite.color.set_a( alpha );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ぶつかった壁のマスを光らせる
+pub fn flash_bump
+(   mut evt_bumped: EventReader<player::Bumped>,
+    mut cmds: Commands,
+)
+{   for bumped in evt_bumped.iter()
+    {   let custom_size = Some ( SIZE_SPRITE_BUMP_FLASH );
+        let color = COLOR_SPRITE_BUMP_FLASH.with_a( ALPHA_SPRITE_BUMP_FLASH );
+        let translation = bumped.cell.to_minimap_center().truncate().extend( DEPTH_SPRITE_MINIMAP_TRAIL );
+        let timer = Timer::from_seconds( SECS_BUMP_FLASH_FADE, TimerMode::Once );
+
+        cmds.spawn( ( SpriteBundle::default(), BumpFlash ( timer ) ) )
+        .insert( Sprite { custom_size, color, ..default() } )
+        .insert( Transform::from_translation( translation ) )
+        ;
+    }
+}
+
+//壁にぶつかった印を素早く薄くして、最後に削除する
+pub fn fade_bump_flashes
+(   mut qry_flash: Query<( Entity, &mut Sprite, &mut BumpFlash )>,
+    time: Res<Time>,
+    mut cmds: Commands,
+)
+{   for ( id, mut sprite, mut flash ) in qry_flash.iter_mut()
+    {   let timer = &mut flash.0;
+        if timer.tick( time.delta() ).finished()
+        {   cmds.entity( id ).despawn_recursive();
+            continue;
+        }
+
+        let alpha = ALPHA_SPRITE_BUMP_FLASH * timer.percent_left();
+        sprite.color.set_a( alpha );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 //プレイヤーの左右旋回に合わせ三角形を回転する
 pub fn turn_player
 (   mut qry_minimap_player: Query<&mut Transform, With<MinimapPlayer>>,
@@ -193,14 +539,69 @@ pub fn turn_player
 
 ////////////////////////////////////////////////////////////////////////////////
 
+//ミニマップの向きを切り替える(トグル動作)
+pub fn toggle_orientation
+(   mut orientation: ResMut<MinimapOrientation>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   if ! inkey.just_pressed( KEY_TOGGLE_ORIENTATION ) { return }
+
+    *orientation = match *orientation
+    {   MinimapOrientation::NorthUp   => MinimapOrientation::HeadingUp,
+        MinimapOrientation::HeadingUp => MinimapOrientation::NorthUp,
+    };
+}
+
+//ヘディングアップの場合、三角形と同じだけカメラを回転して三角形を常に上向きに見せる
+//（三角形はプレイヤーの旋回と同期してアニメーションしている）
+#[allow(clippy::type_complexity)]
+pub fn rotate_camera
+(   qry_minimap_player: Query<&Transform, ( With<MinimapPlayer>, Without<MinimapCamera> )>,
+    mut qry_minimap_camera: Query<&mut Transform, ( With<MinimapCamera>, Without<MinimapPlayer> )>,
+    orientation: Res<MinimapOrientation>,
+)
+{   let Ok ( triangle ) = qry_minimap_player.get_single() else { return };
+    let Ok ( mut camera ) = qry_minimap_camera.get_single_mut() else { return };
+
+    let rotation = match *orientation
+    {   MinimapOrientation::NorthUp   => Quat::IDENTITY,
+        MinimapOrientation::HeadingUp => triangle.rotation,
+    };
+    if camera.rotation != rotation { camera.rotation = rotation }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//キー入力でミニマップを拡大縮小する
+pub fn zoom_camera
+(   mut qry_minimap_camera: Query<&mut OrthographicProjection, With<MinimapCamera>>,
+    mut zoom: ResMut<MinimapZoom>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   let Ok ( mut projection ) = qry_minimap_camera.get_single_mut() else { return };
+
+    //拡大率の段階を変更する
+    let level = zoom.0;
+    if inkey.any_just_pressed( KEYS_ZOOM_IN  ) { zoom.0 = zoom.0.saturating_sub( 1 ) }
+    if inkey.any_just_pressed( KEYS_ZOOM_OUT ) { zoom.0 = ( zoom.0 + 1 ).min( ZOOM_LEVELS.len() - 1 ) }
+    if zoom.0 == level { return }
+
+    projection.scale = ZOOM_LEVELS[ zoom.0 ];
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 //プレイヤー前進後退に合わせてミニマップを逆方向へずらすため、カメラを移動する
+#[allow(clippy::type_complexity)]
 pub fn move_camera
-(   mut qry_minimap_camera: Query<&mut Transform, With<MinimapCamera>>,
+(   mut qry_minimap_camera: Query<&mut Transform, ( With<MinimapCamera>, Without<MinimapPlayer> )>,
+    mut qry_minimap_player: Query<&mut Transform, ( With<MinimapPlayer>, Without<MinimapCamera> )>,
     opt_player: Option<Res<player::Player>>,
     time: Res<Time>,
     mut flag_ongoing: Local<bool>, //default false 停止
 )
 {   let Ok ( mut camera ) = qry_minimap_camera.get_single_mut() else { return };
+    let Ok ( mut triangle ) = qry_minimap_player.get_single_mut() else { return };
     let Some ( player ) = opt_player else { return };
 
     //前進後退でないなら
@@ -231,11 +632,25 @@ pub fn move_camera
 
         *flag_ongoing = true; //行動中
     }
+
+    //プレイヤーの三角形をカメラの中心に合わせる
+    triangle.translation = camera.translation.truncate().extend( DEPTH_SPRITE_MINIMAP_PLAYER );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ミニマップ用2Dカメラのワールド座標から、マップのマス目を逆算する
+pub fn world_to_cell( world: Vec2 ) -> IVec2
+{   let x = ( world.x / PIXELS_PER_GRID ).floor() as i32 - SCREEN_GRIDS_WIDTH; //X軸で１画面分ずれている
+    let y = ( -world.y / PIXELS_PER_GRID ).floor() as i32; //Y軸は符号が反転する
+    let adjuster = SCREEN_FRAME.minimap.size / 2; //奇数／２の場合の端数は切り捨て
+
+    IVec2::new( x, y ) - adjuster
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 
-trait MinimapTrait
+pub trait MinimapTrait
 {   //ミニマップ用2Dカメラの座標を計算する
     fn to_minimap_center( &self ) -> Vec3;
 }
@@ -254,4 +669,24 @@ impl MinimapTrait for IVec2
 
 ////////////////////////////////////////////////////////////////////////////////
 
+//暗闇のマスにいる間はミニマップと全体マップを映さない
+#[allow(clippy::type_complexity)]
+pub fn hide_in_dark_zone
+(   mut qry_minimap: Query<&mut Camera, ( With<MinimapCamera>, Without<fullmap::FullmapCamera> )>,
+    mut qry_fullmap: Query<&mut Camera, With<fullmap::FullmapCamera>>,
+    map: Res<map::Map>,
+    player: Res<player::Player>,
+)
+{   let is_dark = map.is_darkzone( player.position );
+
+    if let Ok ( mut camera ) = qry_minimap.get_single_mut()
+    {   if camera.is_active == is_dark { camera.is_active = ! is_dark }
+    }
+    if let Ok ( mut camera ) = qry_fullmap.get_single_mut()
+    {   if is_dark && camera.is_active { camera.is_active = false }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 //End of code.
\ No newline at end of file
@@ -0,0 +1,333 @@
+use super::*;
+use minimap::MinimapTrait;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//モンスターの行動パターン
+#[derive( Clone, Copy, PartialEq, Eq, Debug )]
+pub enum Behavior
+{   RandomWalk,       //でたらめに歩く
+    Patrol ( IVec2 ), //袋小路の間を巡回する（目的地の袋小路）
+    Chase,            //Playerを追いかける
+}
+
+//モンスターのComponent（Playerと同じマス目の上を一マスずつ動く）
+//（Playerの歩数に合わせて動くので、CONTINUEやリプレイでも同じ動きになる）
+#[derive( Component )]
+pub struct Monster
+{   pub id       : usize,          //spawnした順番（動かす順番）
+    pub position : IVec2,          //位置
+    pub direction: News,           //向き
+    pub behavior : Behavior,       //今の行動パターン
+    pub status   : combat::Status, //戦闘の能力値
+    pub rest     : u32,            //Playerを追わない残り歩数（逃げられた後）
+    routine      : Behavior,       //Playerを見失ったら戻る行動パターン
+    from         : IVec2,          //移動を開始した時の位置
+    progress     : f32,            //移動の進み具合(0.0～1.0)
+}
+
+//ミニマップ上のモンスターのComponent（3Dのモンスターを指す）
+#[derive( Component )]
+pub struct MonsterMarker ( pub Entity );
+
+//モンスター全体で使うResource
+#[derive( Resource, Default )]
+pub struct Monsters
+{   deadends: Vec<IVec2>, //巡回の目的地になる袋小路
+}
+
+//セーブデータに残すモンスターの状態（倒したモンスターは残さない）
+#[derive( Clone, Serialize, Deserialize )]
+pub struct MonsterState
+{   id       : usize,               //spawnした順番
+    position : [ i32; 2 ],          //位置
+    direction: News,                //向き
+    hp       : i32,                 //体力
+    rest     : u32,                 //Playerを追わない残り歩数
+    patrol   : Option<[ i32; 2 ]>,  //巡回の目的地（でたらめに歩くならNone）
+}
+
+//モンスターの設定
+const MONSTER_SEED_SALT    : u64 = 0x4D4F_4E53_5445_5253; //迷路の種から別の乱数列を作る
+const MONSTER_SENSE_RADIUS : i32 = 5;   //Playerに気づく距離(マス)
+const MONSTER_SPAWN_MARGIN : i32 = 6;   //Playerから離して置く距離(マス)
+const MONSTER_MOVE_COEF    : f32 = 2.0; //移動アニメーションの速さ(Playerより遅い)
+const MONSTER_STEP_INTERVAL: u32 = 2;   //Playerが何歩進むごとに一歩動くか
+const COLOR_SPRITE_MINIMAP_MONSTER : Color = Color::CRIMSON;
+const RADIUS_SPRITE_MINIMAP_MONSTER: f32 = PIXELS_PER_GRID * 0.3;
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl Monster
+{   //移動の途中でないか？
+    pub fn is_stop( &self ) -> bool
+    {   self.progress >= 1.0
+    }
+
+    //セーブデータに残す状態
+    pub fn state( &self ) -> MonsterState
+    {   let patrol = match self.routine
+        {   Behavior::Patrol ( goal ) => Some ( goal.to_array() ),
+            _ => None,
+        };
+        MonsterState
+        {   id       : self.id,
+            position : self.position.to_array(),
+            direction: self.direction,
+            hp       : self.status.hp,
+            rest     : self.rest,
+            patrol,
+        }
+    }
+
+    //セーブデータの状態に戻す（移動の途中だったら移動し終えたことにする）
+    fn restore( &mut self, state: &MonsterState )
+    {   self.position  = IVec2::from_array( state.position );
+        self.direction = state.direction;
+        self.status.hp = state.hp;
+        self.rest      = state.rest;
+        self.routine   = state.patrol.map_or( Behavior::RandomWalk, | goal | Behavior::Patrol ( IVec2::from_array( goal ) ) );
+        self.behavior  = self.routine;
+        self.from      = self.position;
+        self.progress  = 1.0;
+    }
+
+    //次に進むマスを決める（他のモンスターとPlayerのマスには入らない）
+    fn next_cell
+    (   &mut self,
+        map: &map::Map,
+        target: IVec2,
+        occupied: &HashSet<IVec2>,
+        deadends: &[ IVec2 ],
+        rng: &mut StdRng,
+    ) -> Option<IVec2>
+    {   //感知範囲内のPlayerへ経路があれば追いかける（逃げられた後はしばらく追わない）
+        let distance = ( target - self.position ).abs();
+        let chase = if distance.x + distance.y <= MONSTER_SENSE_RADIUS && self.rest == 0
+        {   map.find_path( self.position, target )
+                .filter( | route | route.len() as i32 <= MONSTER_SENSE_RADIUS )
+        }
+        else
+        {   None
+        };
+
+        let next = if let Some ( route ) = chase
+        {   self.behavior = Behavior::Chase;
+            route.first().copied()
+        }
+        else
+        {   if self.behavior == Behavior::Chase { self.behavior = self.routine }
+
+            match self.behavior
+            {   Behavior::Patrol ( goal ) =>
+                {   //目的地に着いたら（行けなければ）次の袋小路を選ぶ
+                    let route = map.find_path( self.position, goal ).filter( | route | ! route.is_empty() );
+                    if route.is_none()
+                    {   let goal = deadends[ rng.gen_range( 0..deadends.len() ) ];
+                        self.behavior = Behavior::Patrol ( goal );
+                        self.routine  = self.behavior;
+                    }
+                    route.and_then( | route | route.first().copied() )
+                }
+                _ =>
+                {   //来た道へは、行き止まりでなければ戻らない
+                    let back = self.direction.back();
+                    let mut sides = map.get_sides_space( self.position );
+                    if sides.len() > 1 { sides.retain( | &news | news != back ) }
+                    sides.retain( | &news | ! map.is_noentry( self.position + news ) );
+                    if sides.is_empty() { return None }
+                    Some ( self.position + sides[ rng.gen_range( 0..sides.len() ) ] )
+                }
+            }
+        };
+
+        next.filter( | cell | ! occupied.contains( cell ) && *cell != target )
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ステージ開始時にモンスターをspawnする（数は難易度で決まる）
+type WithMonsterOrMarker = Or<( With<Monster>, With<MonsterMarker> )>;
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_entity
+(   qry_monster: Query<Entity, WithMonsterOrMarker>,
+    mut monsters: ResMut<Monsters>,
+    map: Res<map::Map>,
+    player: Res<player::Player>,
+    stage: Res<stage::Stage>,
+    fog: Res<minimap::MinimapFog>,
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials_standard: ResMut<Assets<StandardMaterial>>,
+    mut materials_color: ResMut<Assets<ColorMaterial>>,
+    opt_loaded: Option<Res<save::LoadedGame>>,
+)
+{   //既存のモンスターがあれば削除する
+    qry_monster.for_each( | id | cmds.entity( id ).despawn_recursive() );
+
+    //乱数は迷路の種から作る（同じ迷路なら同じ配置になる）
+    let mut rng = StdRng::seed_from_u64( stage.map_seed() ^ MONSTER_SEED_SALT );
+
+    //CONTINUEならセーブデータの状態に戻す（セーブデータにないモンスターは倒したもの）
+    let saved_states = opt_loaded.as_ref().and_then( | loaded | loaded.monsters() );
+
+    //置けるマス（Playerから離れた、進入できる空地）と巡回の目的地
+    let mut candidates = Vec::new();
+    monsters.deadends.clear();
+    for x in map.x_range()
+    {   for y in map.y_range()
+        {   let cell = IVec2::new( x, y );
+            if ! map.is_space( cell ) || map.is_noentry( cell ) { continue }
+
+            if map.is_deadend( cell ) { monsters.deadends.push( cell ) }
+
+            let distance = ( cell - player.position ).abs();
+            if distance.x + distance.y >= MONSTER_SPAWN_MARGIN && cell != map.goal
+            {   candidates.push( cell );
+            }
+        }
+    }
+
+    let mesh = meshes.add( shape::RegularPolygon::new( RADIUS_SPRITE_MINIMAP_MONSTER, 8 ).into() );
+    let material = materials_color.add( ColorMaterial::from( COLOR_SPRITE_MINIMAP_MONSTER ) );
+
+    for i in 0..stage.difficulty().monsters
+    {   if candidates.is_empty() { break }
+        let position = candidates.swap_remove( rng.gen_range( 0..candidates.len() ) );
+
+        //半分は袋小路の間を巡回し、残りはでたらめに歩く
+        let routine = if i % 2 == 1 && ! monsters.deadends.is_empty()
+        {   let deadends = &monsters.deadends;
+            Behavior::Patrol ( deadends[ rng.gen_range( 0..deadends.len() ) ] )
+        }
+        else
+        {   Behavior::RandomWalk
+        };
+
+        let sides = map.get_sides_space( position );
+        let direction = sides[ rng.gen_range( 0..sides.len() ) ];
+        let mut monster = Monster
+        {   id      : i,
+            position,
+            direction,
+            behavior: routine,
+            status  : stage.difficulty().monster,
+            rest    : 0,
+            routine,
+            from    : position,
+            progress: 1.0,
+        };
+        if let Some ( states ) = saved_states
+        {   let Some ( state ) = states.iter().find( | state | state.id == i ) else { continue };
+            monster.restore( state );
+        }
+        let ( position, direction ) = ( monster.position, monster.direction );
+
+        //モンスターの姿をspawnする
+        let transform = Transform::from_translation( position.to_3dxz() ).with_rotation( direction.to_quat_y() );
+        let id = cmds.spawn( ( PbrBundle::default(), monster ) )
+        .insert( materials_standard.add( Color::NONE.into() ) ) //透明
+        .insert( transform )
+        .with_children
+        (   | mut cmds |
+            cmds.spawn_monster_figure( &mut meshes, &mut materials_standard )
+        )
+        .id();
+
+        //ミニマップにモンスターの印をspawnする（霧の中では見えない）
+        let marker = MaterialMesh2dBundle
+        {   mesh: mesh.clone().into(),
+            material: material.clone(),
+            visibility: if fog.is_disabled || map.is_explored( position ) { Visibility::Visible } else { Visibility::Hidden },
+            ..default()
+        };
+        let translation = position.to_minimap_center().truncate().extend( DEPTH_SPRITE_MINIMAP_MONSTER );
+        cmds.spawn( ( marker, MonsterMarker ( id ) ) )
+        .insert( Transform::from_translation( translation ) )
+        ;
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//Playerが一マス進み終えたら、決まった歩数ごとにモンスターを一マス動かす
+//（乱数は迷路の種と歩数から作るので、CONTINUEやリプレイでも同じ動きになる）
+pub fn step_monsters
+(   mut evt_step: EventReader<player::StepFinished>,
+    mut qry_monster: Query<&mut Monster>,
+    monsters: Res<Monsters>,
+    map: Res<map::Map>,
+    player: Res<player::Player>,
+    record: Res<hud::PlayRecord>,
+    stage: Res<stage::Stage>,
+)
+{   //同じフレームに複数の歩数が届いたら、古い順に一歩ずつの歩数を割り当てる
+    let count = evt_step.iter().count() as u32;
+    for offset in ( 0..count ).rev()
+    {   let steps = record.stage_steps.saturating_sub( offset );
+
+        //追わない残り歩数を減らす
+        qry_monster.for_each_mut( | mut monster | monster.rest = monster.rest.saturating_sub( 1 ) );
+
+        if steps % MONSTER_STEP_INTERVAL != 0 { continue }
+        let mut rng = StdRng::seed_from_u64( stage.map_seed() ^ MONSTER_SEED_SALT ^ steps as u64 );
+
+        //spawnした順に動かす（Queryの順番に左右されない）
+        let mut list: Vec<_> = qry_monster.iter_mut().collect();
+        list.sort_by_key( | monster | monster.id );
+        let mut occupied: HashSet<_> = list.iter().map( | monster | monster.position ).collect();
+
+        for monster in list.iter_mut()
+        {   let Some ( next ) = monster.next_cell( &map, player.position, &occupied, &monsters.deadends, &mut rng )
+            else { continue };
+            let Some ( news ) = NEWS.into_iter().find( | &news | monster.position + news == next ) else { continue };
+            occupied.remove( &monster.position );
+            occupied.insert( next );
+            monster.from = monster.position;
+            monster.position = next;
+            monster.direction = news;
+            monster.progress = 0.0;
+        }
+    }
+}
+
+//モンスターの姿とミニマップの印を、移動の進み具合に合わせて動かす
+#[allow(clippy::type_complexity)]
+pub fn move_monsters
+(   mut qry_monster: Query<( &mut Monster, &mut Transform )>,
+    mut qry_marker: Query<( &MonsterMarker, &mut Transform, &mut Visibility ), Without<Monster>>,
+    map: Res<map::Map>,
+    fog: Res<minimap::MinimapFog>,
+    time: Res<Time>,
+)
+{   let time_delta = time.delta().as_secs_f32();
+
+    for ( mut monster, mut transform ) in qry_monster.iter_mut()
+    {   if ! monster.is_stop()
+        {   //移動中（中間アニメーション）
+            monster.progress = ( monster.progress + time_delta * MONSTER_MOVE_COEF ).min( 1.0 );
+        }
+
+        let t = monster.progress;
+        transform.translation = monster.from.to_3dxz().lerp( monster.position.to_3dxz(), t );
+        transform.rotation = monster.direction.to_quat_y();
+    }
+
+    //ミニマップの印を3Dのモンスターに合わせる
+    for ( MonsterMarker ( id ), mut transform, mut visibility ) in qry_marker.iter_mut()
+    {   let Ok ( ( monster, _ ) ) = qry_monster.get( *id ) else { continue };
+
+        let from = monster.from.to_minimap_center().truncate();
+        let to = monster.position.to_minimap_center().truncate();
+        transform.translation = from.lerp( to, monster.progress ).extend( DEPTH_SPRITE_MINIMAP_MONSTER );
+
+        let is_visible = fog.is_disabled || map.is_explored( monster.position );
+        let new_visibility = if is_visible { Visibility::Visible } else { Visibility::Hidden };
+        if *visibility != new_visibility { *visibility = new_visibility }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
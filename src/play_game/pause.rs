@@ -0,0 +1,59 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ポーズの設定
+const KEY_PAUSE: KeyCode = KeyCode::P;
+
+counted_array!
+(   const TEXT_PAUSE: [ init_app::MessageSect; _ ] =
+    [   ( "PAUSE", ASSETS_FONT_ORBITRON_BLACK, PIXELS_PER_GRID * 2.0, Color::SILVER ),
+    ]
+);
+
+counted_array!
+(   const PAUSE_MENU: [ menu::MenuItem; _ ] =
+    [   menu::MenuItem::Resume,
+        menu::MenuItem::GiveUp,
+    ]
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+//[P]キーでポーズする
+pub fn catch_input_keyboard
+(   mut next_state: ResMut<NextState<MyState>>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   if inkey.just_pressed( KEY_PAUSE ) { next_state.set( MyState::Pause ) }
+}
+
+//ポーズ画面をspawnする
+pub fn spawn_overlay
+(   mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+)
+{   let menu = menu::Menu::new( &PAUSE_MENU, &[] );
+    menu::spawn_overlay( &mut cmds, &asset_svr, &TEXT_PAUSE, &[], menu );
+}
+
+//決定された項目に応じて遷移する（[P]キーでもポーズを解除する）
+pub fn select_menu
+(   mut evt_selected: EventReader<menu::MenuSelected>,
+    mut next_state: ResMut<NextState<MyState>>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   if inkey.just_pressed( KEY_PAUSE ) { next_state.set( MyState::MainLoop ) }
+
+    for menu::MenuSelected ( item ) in evt_selected.iter()
+    {   match item
+        {   menu::MenuItem::Resume => next_state.set( MyState::MainLoop ),
+            menu::MenuItem::GiveUp => next_state.set( MyState::GameOver ),
+            _ => (),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -10,7 +10,19 @@ pub struct Player
     pub in_action: InAction, //行動の種類
 }
 
-#[derive( Clone, Copy, Default, PartialEq )]
+//一マス進み終えたことを知らせるEvent（罠と仕掛けの発動に使う）
+#[derive( Event )]
+pub struct StepFinished ( pub IVec2 );
+
+//進入禁止のマスへ進もうとして止められたことを知らせるEvent
+//（入力の取りこぼしと区別できるよう、揺れ・ミニマップ・効果音で知らせる）
+#[derive( Event )]
+pub struct Bumped
+{   pub cell     : IVec2, //進めなかったマス
+    pub direction: News,  //進もうとした方角
+}
+
+#[derive( Clone, Copy, Default, PartialEq, Serialize, Deserialize, Debug )]
 pub enum InAction
 {   #[default] Stop,
     TurnRight, TurnLeft, //左右回転
@@ -30,6 +42,28 @@ impl Player
     pub fn is_move( &self ) -> bool
     {   self.in_action == InAction::Forward || self.in_action == InAction::Backward
     }
+
+    //行動を開始する（進入禁止のマスへは移動しない）
+    //行動を開始できたらtrueを返す
+    pub fn start_action( &mut self, action: InAction, map: &map::Map ) -> bool
+    {   match action
+        {   InAction::TurnRight => self.direction = self.direction.turn_right(),
+            InAction::TurnLeft  => self.direction = self.direction.turn_left(),
+            InAction::Forward | InAction::Backward =>
+            {   let news = match action
+                {   InAction::Forward => self.direction,
+                    _                 => self.direction.back(),
+                };
+                let next = self.position + news;
+                if map.is_noentry( next ) { return false }
+                self.position = next;
+            }
+            InAction::Stop => return false,
+        }
+        self.in_action = action;
+
+        true
+    }
 }
 
 // プレイヤーの構造
@@ -53,27 +87,68 @@ pub struct FppCamera; //一人称視点カメラ
 #[derive( Component )]
 pub struct TppCamera; //三人称視点カメラ
 
+//一人称視点⇔三人称視点の切替アニメーションのResource
+//（切替の途中は三人称視点カメラを動かして描画する）
+#[derive( Resource, Default )]
+pub struct CameraTransition
+{   pub timer : Option<Timer>, //切替中ならSome
+    pub from  : Transform,     //切替開始時のカメラの姿勢(PlayerEntityからの相対)
+    pub to_tpp: bool,          //三人称視点へ切り替える途中ならtrue
+}
+
+impl CameraTransition
+{   //切替中か？
+    pub fn is_running( &self ) -> bool
+    {   self.timer.is_some()
+    }
+
+    //切替を開始する
+    pub fn start( &mut self, from: Transform, to_tpp: bool )
+    {   let timer = Some ( Timer::from_seconds( SECS_CAMERA_TRANSITION, TimerMode::Once ) );
+        *self = Self { timer, from, to_tpp };
+    }
+}
+
+//壁にぶつかった時の揺れの設定
+const SECS_BUMP_NUDGE : f32 = 0.18; //前へ出て戻るまでの秒数
+const BUMP_NUDGE_DEPTH: f32 = 0.08; //前へ出る距離（マス目の幅に対する割合）
+
+//壁にぶつかった時の揺れのResource
+#[derive( Resource, Default )]
+pub struct BumpNudge
+{   pub timer    : Option<Timer>, //揺れている途中ならSome
+    pub direction: Vec3,          //揺れる方向(PlayerEntityからの相対)
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
-//Playerの3Dオブジェクトをspawnする
+//Playerをスタート地点に置き、向きをランダムに決める
+pub fn init_player
+(   mut player: ResMut<Player>,
+    mut map: ResMut<map::Map>,
+)
+{   let sides = map.get_sides_space( map.start );
+    let side = sides[ map.rng.gen_range( 0..sides.len() ) ];
+    let direction = side;
+    *player = Player { position: map.start, direction, ..default() };
+}
+
+//Playerの3Dオブジェクトをspawnする（位置と向きはPlayerのResourceに従う）
 #[allow(clippy::too_many_arguments)]
 pub fn spawn_entity
 (   qry_entity: Query<Entity, With<PlayerEntity>>,
-    mut player: ResMut<Player>,
-    mut map: ResMut<map::Map>,
+    player: Res<Player>,
     mut orbit_camera: ResMut<OrbitCamera>,
+    mut transition: ResMut<CameraTransition>,
+    mut nudge: ResMut<BumpNudge>,
     mut cmds: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials_standard: ResMut<Assets<StandardMaterial>>,
 )
 {   //既存のPlayerがあれば削除する
     qry_entity.for_each( | id | cmds.entity( id ).despawn_recursive() );
-
-    //Playerの設定
-    let sides = map.get_sides_space( map.start );
-    let side = sides[ map.rng.gen_range( 0..sides.len() ) ];
-    let direction = side;
-    *player = Player { position: map.start, direction, ..default() };
+    *transition = CameraTransition::default(); //切替の途中なら中止する
+    *nudge = BumpNudge::default(); //揺れの途中なら中止する
 
     let player_position  = player.position.to_3dxz();
     let player_direction = player.direction.to_quat_y();
@@ -82,7 +157,10 @@ pub fn spawn_entity
     let is_active = true;
 
     //三人称視点カメラ(極座標カメラ)の設定
-    *orbit_camera = OrbitCamera { is_active: ! is_active, ..default() };
+    //Playerの背後から見下ろす位置に置く（φはPlayerの向きと同じ角度で背後になる）
+    let ( phi, _, _ ) = player_direction.to_euler( EulerRot::YXZ );
+    let orbit = Orbit { r: TPP_CAMERA_INIT_R, theta: TPP_CAMERA_INIT_THETA, phi: phi.rem_euclid( TAU ) };
+    *orbit_camera = OrbitCamera { orbit, is_active: ! is_active, ..default() };
     let orbit_position = orbit_camera.orbit.to_vec3();
 
     //透明な箱をspawnし、それを親にして中に子をspawnする
@@ -118,46 +196,116 @@ pub fn spawn_entity
 
 ////////////////////////////////////////////////////////////////////////////////
 
-//キー入力によって自機の位置と向きを更新する
+//正面のマスを調べるキー
+const KEY_INTERACT: KeyCode = KeyCode::Return;
+
+//扉を開けようとした時のメッセージ
+const TEXT_DOOR_LOCKED: [ &str; 2 ] = [ "THE DOOR IS LOCKED.", "YOU NEED A KEY." ];
+const TEXT_DOOR_OPENED: [ &str; 2 ] = [ "YOU USED A KEY.", "THE DOOR SWUNG OPEN." ];
+
+//キー入力をPlayerへの指示にする
 pub fn catch_input_keyboard
-(   mut player: ResMut<Player>,
+(   mut evt_command: EventWriter<replay::PlayerCommand>,
+    player: Res<Player>,
     map: Res<map::Map>,
-    orbit_camera: Res<OrbitCamera>,
     inkey: Res<Input<KeyCode>>,
 )
-{   //三人称視点カメラがアクティブなら、入力を受け付けない
-    if orbit_camera.is_active { return }
-
-    //Playerが停止していない場合、入力を受け付けない
+{   //Playerが停止していない場合、入力を受け付けない
     if ! player.is_stop() { return }
 
+    //正面の宝箱や扉を開ける（[Alt]＋[Enter]はフルスクリーン切替なので除く）
+    let is_alt_pressed = inkey.any_pressed( [ KeyCode::AltLeft, KeyCode::AltRight ] );
+    if inkey.just_pressed( KEY_INTERACT ) && ! is_alt_pressed
+    {   evt_command.send( replay::PlayerCommand::Interact );
+    }
+
     //自機の位置と向きを更新する
     for keycode in inkey.get_just_pressed()
-    {   match keycode
-        {   KeyCode::Right =>
-            {   player.direction = player.direction.turn_right();
-                player.in_action = InAction::TurnRight;
-            }
-            KeyCode::Left =>
-            {   player.direction = player.direction.turn_left();
-                player.in_action = InAction::TurnLeft;
+    {   let action = match keycode
+        {   KeyCode::Right => InAction::TurnRight,
+            KeyCode::Left  => InAction::TurnLeft,
+            KeyCode::Up    => InAction::Forward,
+            KeyCode::Down  => InAction::Backward,
+            _ => continue,
+        };
+
+        //鍵のかかった扉へは進まず、鍵を使って開けようとする
+        if action == InAction::Forward && map.is_lockeddoor( player.position + player.direction )
+        {   evt_command.send( replay::PlayerCommand::Interact );
+            continue;
+        }
+
+        evt_command.send( replay::PlayerCommand::Action ( action ) );
+    }
+}
+
+//Playerへの指示を実行し、実行できたものをリプレイに記録する
+#[allow(clippy::too_many_arguments)]
+pub fn apply_commands
+(   mut evt_command: EventReader<replay::PlayerCommand>,
+    mut player: ResMut<Player>,
+    mut map: ResMut<map::Map>,
+    mut record: ResMut<hud::PlayRecord>,
+    mut recorder: ResMut<replay::Recorder>,
+    mut stats: ResMut<stats::PlayerStats>,
+    mut evt_triggered: EventWriter<cell_events::CellTriggered>,
+    mut evt_bumped: EventWriter<Bumped>,
+    mut queue: ResMut<message::MessageQueue>,
+    mut evt_sfx: EventWriter<sound::PlaySfx>,
+    stage: Res<stage::Stage>,
+    asset_svr: Res<AssetServer>,
+    tables: Res<Assets<items::ItemTable>>,
+)
+{   for &command in evt_command.iter()
+    {   let is_done = match command
+        {   replay::PlayerCommand::Action ( action ) =>
+            {   let is_started = player.start_action( action, &map );
+                if is_started { record.count_action( action ) }
+
+                //旋回の音（足音は進み終えた時に鳴らす）
+                let is_turn = action == InAction::TurnRight || action == InAction::TurnLeft;
+                if is_started && is_turn { evt_sfx.send( sound::PlaySfx ( sound::Sfx::Turn ) ) }
+
+                //進めなかったら壁にぶつかったことを知らせる
+                let direction = match action
+                {   InAction::Forward  => Some ( player.direction ),
+                    InAction::Backward => Some ( player.direction.back() ),
+                    _                  => None,
+                };
+                if let ( false, Some ( direction ) ) = ( is_started, direction )
+                {   let cell = player.position + direction;
+                    evt_bumped.send( Bumped { cell, direction } );
+                }
+                is_started
             }
-            KeyCode::Up =>
+            replay::PlayerCommand::Interact =>
             {   let front = player.position + player.direction;
-                if ! map.is_noentry( front )
-                {   player.position = front;
-                    player.in_action = InAction::Forward;
+                if map.is_lockeddoor( front )
+                {   //鍵を持っていれば扉を開ける（鍵は無くなる）
+                    let is_opened = stats.remove_item( items::ITEM_ID_KEY ) && map.open_door( front );
+                    queue.push( if is_opened { &TEXT_DOOR_OPENED } else { &TEXT_DOOR_LOCKED } );
+                    is_opened
                 }
-            }
-            KeyCode::Down =>
-            {   let back = player.position + player.direction.back();
-                if ! map.is_noentry( back )
-                {   player.position = back;
-                    player.in_action = InAction::Backward;
+                else
+                {   //調べるイベント（宝箱を含む）があれば起こす
+                    let is_interact = map.cell_event( front )
+                        .is_some_and( | event | event.trigger == cell_events::Trigger::Interact );
+                    if is_interact
+                    {   let trigger = cell_events::Trigger::Interact;
+                        evt_triggered.send( cell_events::CellTriggered { cell: front, trigger } );
+                    }
+                    is_interact
                 }
             }
-            _ => (),
-        }
+            replay::PlayerCommand::Select ( menu::MenuItem::Use ( index ) ) =>
+            {   //持ち物画面で使ったアイテム（リプレイの再生）
+                items::table( &asset_svr, &tables )
+                    .is_some_and( | table | stats.use_item( index, table, false ).is_some() )
+            }
+            replay::PlayerCommand::Select ( _ ) => false,
+        };
+
+        if is_done { recorder.record( stage.number, record.stage_elapsed, command ) }
     }
 }
 
@@ -206,6 +354,8 @@ pub fn rotate_player
 pub fn move_player
 (   mut qry_player: Query<&mut Transform, With<PlayerEntity>>,
     mut player: ResMut<Player>,
+    mut evt_step: EventWriter<StepFinished>,
+    mut evt_sfx: EventWriter<sound::PlaySfx>,
     time: Res<Time>,
     mut sum_distance: Local<f32>, //default 0.0
 )
@@ -227,6 +377,10 @@ pub fn move_player
         //情報更新する
         player.in_action = InAction::Stop;
         *sum_distance = 0.0;
+
+        //進み終えたマスの罠と仕掛けを発動させる
+        evt_step.send( StepFinished ( player.position ) );
+        evt_sfx.send( sound::PlaySfx ( sound::Sfx::Footstep ) );
     }
     else
     {   //前進後退する（中間アニメーション）
@@ -246,4 +400,121 @@ pub fn move_player
 
 ////////////////////////////////////////////////////////////////////////////////
 
+//壁にぶつかったら揺れを始める（揺れの途中なら最初からやり直す）
+pub fn start_bump_nudge
+(   mut evt_bumped: EventReader<Bumped>,
+    mut nudge: ResMut<BumpNudge>,
+)
+{   let Some ( bumped ) = evt_bumped.iter().last() else { return };
+
+    let timer = Some ( Timer::from_seconds( SECS_BUMP_NUDGE, TimerMode::Once ) );
+    let direction = ( IVec2::ZERO + bumped.direction ).to_3dxz();
+    *nudge = BumpNudge { timer, direction };
+}
+
+//プレイヤーの姿(中間親)を前へ出して戻す（一人称視点カメラは子なので一緒に揺れる）
+pub fn nudge_figure
+(   mut qry_figure: Query<&mut Transform, With<FigureHead>>,
+    mut nudge: ResMut<BumpNudge>,
+    time: Res<Time>,
+)
+{   let Ok ( mut figure ) = qry_figure.get_single_mut() else { return };
+    let Some ( timer ) = nudge.timer.as_mut() else { return };
+
+    //揺れ終わったら元の位置へピッタリ戻す
+    if timer.tick( time.delta() ).finished()
+    {   figure.translation = Vec3::ZERO;
+        nudge.timer = None;
+        return;
+    }
+
+    //前へ出て戻る（中間アニメーション）
+    let depth = BUMP_NUDGE_DEPTH * ( PI * timer.percent() ).sin();
+    figure.translation = nudge.direction * depth;
+}
+
+//壁にぶつかった音を鳴らす
+pub fn play_bump_sound
+(   mut evt_bumped: EventReader<Bumped>,
+    mut evt_sfx: EventWriter<sound::PlaySfx>,
+)
+{   if evt_bumped.iter().last().is_some()
+    {   evt_sfx.send( sound::PlaySfx ( sound::Sfx::Bump ) );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//三人称視点カメラをPlayerの背後へ追従させる
+//（向きは遅れて追従し、壁に遮られる場合はカメラを手前へ引き寄せる）
+#[allow(clippy::type_complexity)]
+pub fn follow_tpp_camera
+(   mut qry_camera: Query<&mut Transform, ( With<TppCamera>, Without<FigureHead>, Without<PlayerEntity> )>,
+    qry_figure: Query<&Transform, With<FigureHead>>,
+    qry_player: Query<&Transform, With<PlayerEntity>>,
+    mut orbit_camera: ResMut<OrbitCamera>,
+    map: Res<map::Map>,
+    time: Res<Time>,
+    mut distance: Local<f32>, //default 0.0
+)
+{   let Ok ( mut camera ) = qry_camera.get_single_mut() else { return };
+    let Ok ( figure ) = qry_figure.get_single() else { return };
+    let Ok ( player ) = qry_player.get_single() else { return };
+
+    //アクティブでないなら更新しない
+    if ! orbit_camera.is_active { return }
+
+    let time_delta = time.delta().as_secs_f32();
+
+    //φをPlayerの向き(旋回アニメーション中の角度)へ近づける
+    let ( yaw, _, _ ) = figure.rotation.to_euler( EulerRot::YXZ );
+    let orbit = &mut orbit_camera.orbit;
+    let mut diff = ( yaw - orbit.phi ).rem_euclid( TAU );
+    if diff > PI { diff -= TAU } //近い方向へ回す
+    orbit.phi += diff * ( 1.0 - ( -TPP_CAMERA_LAG_COEF * time_delta ).exp() );
+    orbit.phi = orbit.phi.rem_euclid( TAU );
+
+    //注視点から見たカメラの方向と、壁に遮られない距離
+    let offset = orbit.to_vec3();
+    let look_at = orbit_camera.look_at;
+    let origin = player.translation + look_at;
+    let limit = distance_to_wall( &map, origin, offset );
+
+    //壁に近づく時は即座に、離れる時はゆっくり距離を戻す
+    if *distance <= 0.0 || limit < *distance
+    {   *distance = limit;
+    }
+    else
+    {   *distance += ( limit - *distance ) * ( 1.0 - ( -TPP_CAMERA_BACK_COEF * time_delta ).exp() );
+    }
+
+    //カメラの位置と向きを更新する（Playerの子なので相対位置）
+    let vec3 = offset.normalize_or_zero() * *distance + look_at;
+    *camera = Transform::from_translation( vec3 ).looking_at( look_at, Vec3::Y );
+}
+
+//注視点からカメラ方向へマス目を辿り、壁に遮られない距離を返す
+fn distance_to_wall( map: &map::Map, origin: Vec3, offset: Vec3 ) -> f32
+{   let length = offset.length();
+    let direction = offset.normalize_or_zero();
+
+    let mut d = 0.0;
+    while d < length
+    {   d = ( d + TPP_CAMERA_RAY_STEP ).min( length );
+        let point = origin + direction * d;
+
+        //壁の上面より高ければ遮られない（カメラは上方にあるので以降も同じ）
+        if point.y >= TPP_CAMERA_WALL_TOP { break }
+
+        let cell = IVec2::new( point.x.round() as i32, point.z.round() as i32 );
+        if map.is_wall( cell )
+        {   return ( d - TPP_CAMERA_WALL_GAP ).max( TPP_CAMERA_WALL_GAP );
+        }
+    }
+
+    length
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 //End of code.
\ No newline at end of file
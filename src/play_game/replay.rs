@@ -0,0 +1,202 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//Playerへの指示（キー入力・自動歩行・リプレイのどれからも同じEventで送る）
+#[derive( Event, Clone, Copy, PartialEq, Serialize, Deserialize, Debug )]
+pub enum PlayerCommand
+{   Action ( player::InAction ), //旋回・前進・後退
+    Interact,                    //正面の宝箱を開ける
+    Select ( menu::MenuItem ),   //メニューで選んだ項目（戦闘・持ち物・選択肢）
+}
+
+//リプレイの１コマ（ステージ内の経過時間と実行された指示）
+#[derive( Clone, Copy, Serialize, Deserialize )]
+pub struct ReplayCommand
+{   pub stage  : i32,          //ステージ数
+    pub time   : Duration,     //ステージの経過時間
+    pub command: PlayerCommand, //実行された指示
+}
+
+//リプレイ（迷路は種から作り直せるので、指示の列だけで再現できる）
+#[derive( Clone, Default, Serialize, Deserialize )]
+pub struct Replay
+{   pub seed    : u64,                //ゲーム全体の乱数の種
+    pub stage   : i32,                //記録を始めたステージ数
+    pub commands: Vec<ReplayCommand>, //実行された指示の列
+}
+
+//記録中のリプレイのResource
+#[derive( Resource, Default )]
+pub struct Recorder ( pub Replay );
+
+impl Recorder
+{   //実行された指示を記録する
+    pub fn record( &mut self, stage: i32, time: Duration, command: PlayerCommand )
+    {   self.0.commands.push( ReplayCommand { stage, time, command } );
+    }
+}
+
+//再生中のリプレイのResource（これがある間は手動操作を受け付けない）
+#[derive( Resource )]
+pub struct Playback
+{   pub replay: Replay, //再生中のリプレイ
+    cursor    : usize,  //次に送る指示の添字
+    speed     : usize,  //再生速度(PLAYBACK_SPEEDSの添字)
+    wait      : f32,    //メニューの選択を送るまでの経過秒数
+}
+
+impl Playback
+{   //再生速度の倍率
+    pub fn speed( &self ) -> f32
+    {   PLAYBACK_SPEEDS[ self.speed ]
+    }
+
+    //次の指示がこのステージのメニューの選択か？
+    pub fn is_selecting( &self, stage: i32 ) -> bool
+    {   self.replay.commands.get( self.cursor )
+            .is_some_and( | next | next.stage == stage && matches!( next.command, PlayerCommand::Select ( _ ) ) )
+    }
+}
+
+//メニューの選択の間隔（再生速度の倍率がかかる）
+const SECS_PLAYBACK_SELECT: f32 = 1.0;
+
+//再生速度の設定
+const KEY_PLAYBACK_SLOWER: KeyCode = KeyCode::BracketLeft;
+const KEY_PLAYBACK_FASTER: KeyCode = KeyCode::BracketRight;
+counted_array!
+(   const PLAYBACK_SPEEDS: [ f32; _ ] = [ 0.25, 0.5, 1.0, 2.0, 4.0, 8.0 ]
+);
+const PLAYBACK_SPEED_NORMAL: usize = 2;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//リプレイを保存する
+pub fn write( replay: &Replay )
+{   let result = ron::to_string( replay ).map_err( | e | e.to_string() )
+        .and_then( | text | storage::save( STORAGE_KEY_REPLAY, &text ) );
+    if let Err ( e ) = result { warn!( "Failed to save the replay: {e}" ) }
+}
+
+//保存済みのリプレイを読み出す（無い・壊れている場合はNone）
+pub fn read() -> Option<Replay>
+{   let text = storage::load( STORAGE_KEY_REPLAY )?;
+    ron::from_str( &text ).map_err( | e | warn!( "Broken replay data: {e}" ) ).ok()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ゲーム開始時に記録を始める
+//（CONTINUEなら同じ種のリプレイの続きから記録する）
+pub fn start_recording
+(   mut recorder: ResMut<Recorder>,
+    stage: Res<stage::Stage>,
+    opt_continue: Option<Res<ContinueGame>>,
+)
+{   let saved = opt_continue.and_then( | _ | read() ).filter( | replay | replay.seed == stage.seed );
+    recorder.0 = saved.unwrap_or( Replay { seed: stage.seed, stage: stage.number, ..default() } );
+}
+
+//記録中のリプレイを保存する
+pub fn save_recording( recorder: Res<Recorder> )
+{   write( &recorder.0 );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//タイトルでREPLAYが選ばれたら、リプレイの種とステージで始めて再生を開始する
+pub fn start_playback
+(   mut stage: ResMut<stage::Stage>,
+    mut time: ResMut<Time>,
+    mut cmds: Commands,
+)
+{   cmds.remove_resource::<WatchReplay>();
+
+    let Some ( replay ) = read() else { return };
+    *stage = stage::Stage { number: replay.stage, seed: replay.seed };
+    time.set_relative_speed( PLAYBACK_SPEEDS[ PLAYBACK_SPEED_NORMAL ] );
+    cmds.insert_resource( Playback { replay, cursor: 0, speed: PLAYBACK_SPEED_NORMAL, wait: 0.0 } );
+}
+
+//メニューで選ばれた項目を返す
+//（再生中は記録された選択を間隔をあけて返し、手動の選択は捨てる。記録が尽きたら手動に戻す）
+pub fn select_item
+(   evt_selected: &mut EventReader<menu::MenuSelected>,
+    opt_playback: Option<&mut Playback>,
+    stage: i32,
+    time: &Time,
+) -> Option<menu::MenuItem>
+{   let Some ( playback ) = opt_playback.filter( | playback | playback.is_selecting( stage ) ) else
+    {   return evt_selected.iter().last().map( | menu::MenuSelected ( item ) | *item )
+    };
+    evt_selected.clear();
+
+    playback.wait += time.delta_seconds();
+    if playback.wait < SECS_PLAYBACK_SELECT { return None }
+    playback.wait = 0.0;
+
+    let PlayerCommand::Select ( item ) = playback.replay.commands[ playback.cursor ].command else { return None };
+    playback.cursor += 1;
+    Some ( item )
+}
+
+//記録された時刻になったら指示を送る（Playerの行動が終わるまでは次を送らない）
+pub fn play_back
+(   opt_playback: Option<ResMut<Playback>>,
+    mut evt_command: EventWriter<PlayerCommand>,
+    mut time: ResMut<Time>,
+    mut cmds: Commands,
+    stage: Res<stage::Stage>,
+    record: Res<hud::PlayRecord>,
+    player: Res<player::Player>,
+)
+{   let Some ( mut playback ) = opt_playback else { return };
+    if ! player.is_stop() { return }
+
+    //最後まで再生したら手動操作に戻す
+    let Some ( &next ) = playback.replay.commands.get( playback.cursor ) else
+    {   time.set_relative_speed( PLAYBACK_SPEEDS[ PLAYBACK_SPEED_NORMAL ] );
+        cmds.remove_resource::<Playback>();
+        return;
+    };
+
+    if next.stage != stage.number || record.stage_elapsed < next.time { return }
+
+    //迷路の中で使ったアイテムだけ送る（戦闘などのメニューの選択は、その画面で読み出す）
+    match next.command
+    {   PlayerCommand::Select ( menu::MenuItem::Use ( _ ) ) | PlayerCommand::Action ( _ ) | PlayerCommand::Interact =>
+            evt_command.send( next.command ),
+        PlayerCommand::Select ( _ ) => warn!( "Skipped a menu selection out of its screen in the replay" ),
+    }
+    playback.cursor += 1;
+}
+
+//[[][]]キーで再生速度を変える
+pub fn change_speed
+(   opt_playback: Option<ResMut<Playback>>,
+    mut time: ResMut<Time>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   let Some ( mut playback ) = opt_playback else { return };
+
+    let speed = if inkey.just_pressed( KEY_PLAYBACK_SLOWER ) { playback.speed.saturating_sub( 1 ) }
+        else if inkey.just_pressed( KEY_PLAYBACK_FASTER ) { ( playback.speed + 1 ).min( PLAYBACK_SPEEDS.len() - 1 ) }
+        else { return };
+
+    playback.speed = speed;
+    time.set_relative_speed( playback.speed() );
+}
+
+//再生を止めて速度を戻す（ゲームオーバー等で再生の途中で抜けた時）
+pub fn stop_playback
+(   mut time: ResMut<Time>,
+    mut cmds: Commands,
+)
+{   time.set_relative_speed( PLAYBACK_SPEEDS[ PLAYBACK_SPEED_NORMAL ] );
+    cmds.remove_resource::<Playback>();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
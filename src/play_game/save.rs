@@ -0,0 +1,145 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//セーブデータ（迷路は種から作り直し、ゲーム中の変化だけを保存する）
+#[derive( Serialize, Deserialize )]
+pub struct SaveData
+{   seed          : u64,          //ゲーム全体の乱数の種
+    stage         : i32,          //ステージ数
+    position      : [ i32; 2 ],   //Playerの位置
+    direction     : News,         //Playerの向き
+    steps         : u32,          //歩数
+    turns         : u32,          //旋回数
+    elapsed       : Duration,     //経過時間
+    explored_cells: String,       //踏査済みのマス
+    #[serde( default )]
+    stage_steps   : u32,          //今のステージの歩数
+    #[serde( default )]
+    stage_elapsed : Duration,     //今のステージの経過時間
+    #[serde( default )]
+    stats         : Option<stats::PlayerStats>, //Playerの能力値と持ち物
+    #[serde( default )]
+    opened_doors  : Vec<[ i32; 2 ]>, //開いた扉のマス
+    #[serde( default )]
+    discovered    : Vec<[ i32; 2 ]>, //見つけた罠のマス
+    #[serde( default )]
+    events_done   : Vec<[ i32; 2 ]>, //済んだ一度だけのイベントのマス
+    #[serde( default )]
+    monsters      : Option<Vec<monster::MonsterState>>, //生きているモンスター（古いセーブデータはNone）
+}
+
+//セーブデータから再開する途中であることを示すResource
+#[derive( Resource )]
+pub struct LoadedGame ( SaveData );
+
+impl LoadedGame
+{   //生きているモンスターの状態（記録がなければNone）
+    pub fn monsters( &self ) -> Option<&[ monster::MonsterState ]>
+    {   self.0.monsters.as_deref()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ゲームの途中経過を保存する
+pub fn save_game
+(   qry_monster: Query<&monster::Monster>,
+    stage: Res<stage::Stage>,
+    player: Res<player::Player>,
+    record: Res<hud::PlayRecord>,
+    stats: Res<stats::PlayerStats>,
+    map: Res<map::Map>,
+)
+{   let data = SaveData
+    {   seed          : stage.seed,
+        stage         : stage.number,
+        position      : player.position.to_array(),
+        direction     : player.direction,
+        steps         : record.steps,
+        turns         : record.turns,
+        elapsed       : record.elapsed,
+        opened_doors  : map.opened_doors().iter().map( IVec2::to_array ).collect(),
+        discovered    : map.discovered_cells().iter().map( IVec2::to_array ).collect(),
+        events_done   : map.finished_events().iter().map( IVec2::to_array ).collect(),
+        explored_cells: map.explored_cells(),
+        stage_steps   : record.stage_steps,
+        stage_elapsed : record.stage_elapsed,
+        stats         : Some ( stats.clone() ),
+        monsters      : Some ( qry_monster.iter().map( monster::Monster::state ).collect() ),
+    };
+
+    let result = ron::to_string( &data ).map_err( | e | e.to_string() )
+        .and_then( | text | storage::save( STORAGE_KEY_SAVEDATA, &text ) );
+    if let Err ( e ) = result { warn!( "Failed to save the game: {e}" ) }
+}
+
+//ゲームオーバーになったらセーブデータを消す（CONTINUEできなくなる）
+pub fn delete_save_data()
+{   storage::remove( STORAGE_KEY_SAVEDATA );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//タイトルでCONTINUEが選ばれたら、セーブデータのステージとプレイ記録を復元する
+pub fn load_game
+(   mut stage: ResMut<stage::Stage>,
+    mut record: ResMut<hud::PlayRecord>,
+    mut stats: ResMut<stats::PlayerStats>,
+    mut cmds: Commands,
+)
+{   cmds.remove_resource::<ContinueGame>();
+
+    let Some ( text ) = storage::load( STORAGE_KEY_SAVEDATA ) else { return };
+    let data: SaveData = match ron::from_str( &text )
+    {   Ok ( data ) => data,
+        Err ( e ) => { warn!( "Broken save data: {e}" ); return }
+    };
+
+    *stage = stage::Stage { number: data.stage, seed: data.seed };
+    *record = hud::PlayRecord
+    {   steps        : data.steps,
+        turns        : data.turns,
+        elapsed      : data.elapsed,
+        stage_steps  : data.stage_steps,
+        stage_elapsed: data.stage_elapsed,
+    };
+    if let Some ( saved ) = data.stats.clone() { *stats = saved }
+
+    //迷路とPlayerはステージの前処理で復元する
+    cmds.insert_resource( LoadedGame ( data ) );
+}
+
+//同じ種で作り直した迷路に、開いた扉・済んだイベント（宝箱を含む）と踏査済みのマスを重ねる
+pub fn restore_map
+(   loaded: Res<LoadedGame>,
+    mut map: ResMut<map::Map>,
+)
+{   let data = &loaded.0;
+    let opened_doors: Vec<_> = data.opened_doors.iter().map( | &xy | IVec2::from_array( xy ) ).collect();
+    let discovered: Vec<_> = data.discovered.iter().map( | &xy | IVec2::from_array( xy ) ).collect();
+    let finished_events: Vec<_> = data.events_done.iter().map( | &xy | IVec2::from_array( xy ) ).collect();
+    map.restore_cells( &opened_doors, &discovered, &finished_events, &data.explored_cells );
+}
+
+//Playerの位置と向きを復元する
+pub fn restore_player
+(   loaded: Res<LoadedGame>,
+    mut player: ResMut<player::Player>,
+    map: Res<map::Map>,
+)
+{   let data = &loaded.0;
+    let position = IVec2::from_array( data.position );
+    if map.is_space( position )
+    {   *player = player::Player { position, direction: data.direction, ..default() };
+    }
+}
+
+//復元が終わったらResourceを消す（モンスターの復元はmonster::spawn_entity()で行う）
+pub fn finish_loading( mut cmds: Commands )
+{   cmds.remove_resource::<LoadedGame>();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -11,6 +11,20 @@ pub trait AddMethodToChildBuilder
         materials: &mut ResMut<Assets<StandardMaterial>>
     );
 
+    //ゴーストの姿をspawnする（Playerと同じ形で半透明）
+    fn spawn_ghost_figure
+    (   &mut self,
+        meshes   : &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>
+    );
+
+    //モンスターの姿をspawnする
+    fn spawn_monster_figure
+    (   &mut self,
+        meshes   : &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>
+    );
+
     //鍵付き宝箱をspawnする
     fn spawn_locked_chest
     (   &mut self,
@@ -19,6 +33,24 @@ pub trait AddMethodToChildBuilder
         meshes   : &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<StandardMaterial>>,
     );
+
+    //開いた宝箱をspawnする
+    fn spawn_opened_chest
+    (   &mut self,
+        position : Vec3,
+        rotation : Quat,
+        meshes   : &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+    );
+
+    //鍵のかかった扉をspawnする
+    fn spawn_locked_door
+    (   &mut self,
+        position : Vec3,
+        rotation : Quat,
+        meshes   : &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+    );
 }
 
 pub trait AddMethodToChildBuilderWith<T> //ジェネリクス付き
@@ -39,17 +71,50 @@ impl AddMethodToChildBuilder for &mut ChildBuilder<'_, '_, '_>
         meshes   : &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<StandardMaterial>>,
     )
-    {   //配置
-        self.spawn( PbrBundle::default() )
-        .insert( meshes.add( shape::UVSphere { radius: 0.4, ..default() }.into() ) )
-        .insert( materials.add( Color::DARK_GRAY.into() ) )
-        .insert( Transform::from_translation( Vec3::ZERO ) )
-        ;
+    {   let back = materials.add( Color::DARK_GRAY.into() );
+        let face = materials.add( Color::YELLOW.into() );
+        spawn_figure( self, meshes, back, face );
+    }
+
+    //ゴーストの姿をspawnする（Playerと同じ形で半透明）
+    fn spawn_ghost_figure
+    (   &mut self,
+        meshes   : &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+    )
+    {   let ghost = | color: Color | StandardMaterial
+        {   base_color: color,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true, //暗いステージでも見えるように
+            ..default()
+        };
+        let back = materials.add( ghost( Color::rgba( 0.3, 0.6, 1.0, 0.25 ) ) );
+        let face = materials.add( ghost( Color::rgba( 0.6, 0.9, 1.0, 0.4 ) ) );
+        spawn_figure( self, meshes, back, face );
+    }
+
+    //モンスターの姿をspawnする
+    fn spawn_monster_figure
+    (   &mut self,
+        meshes   : &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+    )
+    {   //胴体
+        let shape_capsule = shape::Capsule { radius: 0.25, depth: 0.3, ..default() };
         self.spawn( PbrBundle::default() )
-        .insert( meshes.add( shape::UVSphere { radius: 0.395, ..default() }.into() ) )
-        .insert( materials.add( Color::YELLOW.into() ) )
-        .insert( Transform::from_translation( Vec3::NEG_Z * 0.01 ) )
+        .insert( meshes.add( shape_capsule.into() ) )
+        .insert( materials.add( Color::CRIMSON.into() ) )
+        .insert( Transform::from_translation( Vec3::Y * -0.2 ) )
         ;
+
+        //目（正面はNEG_Z）
+        for x in [ -0.1, 0.1 ]
+        {   self.spawn( PbrBundle::default() )
+            .insert( meshes.add( shape::UVSphere { radius: 0.06, ..default() }.into() ) )
+            .insert( materials.add( Color::WHITE.into() ) )
+            .insert( Transform::from_translation( Vec3::new( x, -0.05, -0.22 ) ) )
+            ;
+        }
     }
 
     //鍵付き宝箱をspawnする
@@ -111,6 +176,94 @@ impl AddMethodToChildBuilder for &mut ChildBuilder<'_, '_, '_>
             }
         );
     }
+
+    //開いた宝箱をspawnする
+    fn spawn_opened_chest
+    (   &mut self,
+        position : Vec3,
+        rotation : Quat,
+        meshes   : &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+    )
+    {   self.spawn( PbrBundle::default() )
+        .insert( materials.add( Color::NONE.into() ) ) //透明
+        .insert( Transform::from_translation( position ).with_rotation( rotation ) )
+        .with_children
+        (   | cmds |
+            {   //本体
+                let shape_box = shape::Box::new( 0.7, 0.3, 0.4 );
+                cmds.spawn( PbrBundle::default() )
+                .insert( meshes.add( shape_box.into() ) )
+                .insert( materials.add( Color::MAROON.into() ) )
+                .insert( Transform::from_translation( Vec3::Y * -0.35 ) )
+                ;
+
+                //上蓋（背面側へ開いている）
+                let shape_cylinder = shape::Cylinder { height: 0.695, radius: 0.195, ..default() };
+                let translation = Vec3::Y * -0.1 + Vec3::Z * 0.3;
+                let rotation = Quat::from_rotation_z( PI * 0.5 );
+                let transform = Transform::from_translation( translation ).with_rotation( rotation );
+                cmds.spawn( PbrBundle::default() )
+                .insert( meshes.add( shape_cylinder.into() ) )
+                .insert( materials.add( Color::MAROON.into() ) )
+                .insert ( transform );
+            }
+        );
+    }
+
+    //鍵のかかった扉をspawnする（通路をふさぐ板に錠前を付ける）
+    fn spawn_locked_door
+    (   &mut self,
+        position : Vec3,
+        rotation : Quat,
+        meshes   : &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+    )
+    {   self.spawn( PbrBundle::default() )
+        .insert( materials.add( Color::NONE.into() ) ) //透明
+        .insert( Transform::from_translation( position ).with_rotation( rotation ) )
+        .with_children
+        (   | cmds |
+            {   //扉の板（両面に錠前が見えるよう、板の厚みより少し外に付ける）
+                let shape_box = shape::Box::new( 1.0, 1.0, 0.1 );
+                cmds.spawn( PbrBundle::default() )
+                .insert( meshes.add( shape_box.into() ) )
+                .insert( materials.add( Color::rgb( 0.4, 0.25, 0.1 ).into() ) )
+                .insert( Transform::from_translation( Vec3::ZERO ) )
+                ;
+
+                //錠前
+                for z in [ -0.06, 0.06 ]
+                {   let shape_plate = shape::Box::new( 0.12, 0.16, 0.02 );
+                    cmds.spawn( PbrBundle::default() )
+                    .insert( meshes.add( shape_plate.into() ) )
+                    .insert( materials.add( Color::GOLD.into() ) )
+                    .insert( Transform::from_translation( Vec3::Z * z ) )
+                    ;
+                }
+            }
+        );
+    }
+}
+
+//Player・ゴースト共通の姿（背面と顔の球を重ねる）
+fn spawn_figure
+(   cmds     : &mut ChildBuilder,
+    meshes   : &mut ResMut<Assets<Mesh>>,
+    back     : Handle<StandardMaterial>,
+    face     : Handle<StandardMaterial>,
+)
+{   //配置
+    cmds.spawn( PbrBundle::default() )
+    .insert( meshes.add( shape::UVSphere { radius: 0.4, ..default() }.into() ) )
+    .insert( back )
+    .insert( Transform::from_translation( Vec3::ZERO ) )
+    ;
+    cmds.spawn( PbrBundle::default() )
+    .insert( meshes.add( shape::UVSphere { radius: 0.395, ..default() }.into() ) )
+    .insert( face )
+    .insert( Transform::from_translation( Vec3::NEG_Z * 0.01 ) )
+    ;
 }
 
 impl<T: Component> AddMethodToChildBuilderWith<T> for &mut ChildBuilder<'_, '_, '_>
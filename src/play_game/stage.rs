@@ -0,0 +1,211 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ステージのResource
+#[derive( Resource )]
+pub struct Stage
+{   pub number: i32, //ステージ数(1から)
+    pub seed  : u64, //ゲーム全体の乱数の種
+}
+
+impl Default for Stage
+{   fn default() -> Self
+    {   let seed_dev = 1234567890;
+        let seed_rel = || rand::thread_rng().gen::<u64>();
+        let seed = seed_from_args().unwrap_or_else( || if misc::DEBUG() { seed_dev } else { seed_rel() } );
+
+        Self { number: 1, seed }
+    }
+}
+
+impl Stage
+{   //ステージ毎の乱数の種（ゲームの種とステージ数が同じなら同じ迷路になる）
+    pub fn map_seed( &self ) -> u64
+    {   self.seed ^ ( self.number as u64 ).wrapping_mul( 0x9E37_79B9_7F4A_7C15 )
+    }
+
+    //ステージ数に対応する難易度（表の最後を超えたら最後の難易度が続く）
+    pub fn difficulty( &self ) -> &'static Difficulty
+    {   let index = ( self.number - 1 ).clamp( 0, DIFFICULTY_TABLE.len() as i32 - 1 );
+        &DIFFICULTY_TABLE[ index as usize ]
+    }
+}
+
+//コマンドラインの「--seed=<16進数>」で種を固定する（同じ迷路でタイムを競うため）
+fn seed_from_args() -> Option<u64>
+{   std::env::args().find_map
+    (   | arg | u64::from_str_radix( arg.strip_prefix( "--seed=" )?, 16 ).ok()
+    )
+}
+
+//迷路の作り方
+#[derive( Clone, Copy )]
+pub enum Generator
+{   MazeA1, //穴掘り型１ → 空地拡張型
+    MazeA2, //穴掘り型２ → 空地拡張型
+    MazeB,  //空地拡張型のみ
+}
+
+//難易度
+pub struct Difficulty
+{   pub size      : IVec2,               //マップの大きさ(Grid)
+    pub generators: &'static [ Generator ], //迷路の作り方(ランダムに選ぶ)
+    pub brightness: f32,                 //ライトの明るさ(倍率)
+    pub doors     : usize,               //ゴールへの道をふさぐ扉の数
+    pub teleports : usize,               //テレポーターの数
+    pub dark_zones: usize,               //暗闇の数
+    pub spinners  : usize,               //回転床の数
+    pub pits      : usize,               //落とし穴の数
+    pub monsters  : usize,               //モンスターの数
+    pub monster   : combat::Status,      //モンスターの能力値
+    pub reward    : ( u32, u32 ),        //モンスターを倒した時の経験値とお金
+    pub events    : &'static str,        //イベント表のAsset
+    pub bgm       : &'static str,        //BGM（ステージのテーマ）
+}
+
+//難易度表（ステージ１から順）
+counted_array!
+(   const DIFFICULTY_TABLE: [ Difficulty; _ ] =
+    [   Difficulty
+        {   size: IVec2::new( 15, 15 ),
+            generators: &[ Generator::MazeB ],
+            brightness: 1.0,
+            doors     : 0,
+            teleports : 0,
+            dark_zones: 0,
+            spinners  : 0,
+            pits      : 0,
+            monsters  : 0,
+            monster   : combat::Status { hp: 8, max_hp: 8, attack: 4, defense: 1 },
+            reward    : ( 4, 3 ),
+            events    : ASSETS_DATA_EVENTS_STAGE1,
+            bgm       : ASSETS_SOUND_BGM_THEME1,
+        },
+        Difficulty
+        {   size: IVec2::new( 21, 21 ),
+            generators: &[ Generator::MazeA1, Generator::MazeB ],
+            brightness: 0.9,
+            doors     : 0,
+            teleports : 0,
+            dark_zones: 0,
+            spinners  : 1,
+            pits      : 2,
+            monsters  : 1,
+            monster   : combat::Status { hp: 12, max_hp: 12, attack: 5, defense: 2 },
+            reward    : ( 6, 5 ),
+            events    : ASSETS_DATA_EVENTS_STAGE2,
+            bgm       : ASSETS_SOUND_BGM_THEME1,
+        },
+        Difficulty
+        {   size: IVec2::new( 31, 31 ),
+            generators: &[ Generator::MazeA1, Generator::MazeA2, Generator::MazeB ],
+            brightness: 0.75,
+            doors     : 1,
+            teleports : 1,
+            dark_zones: 1,
+            spinners  : 2,
+            pits      : 3,
+            monsters  : 3,
+            monster   : combat::Status { hp: 16, max_hp: 16, attack: 7, defense: 3 },
+            reward    : ( 9, 8 ),
+            events    : ASSETS_DATA_EVENTS_STAGE3,
+            bgm       : ASSETS_SOUND_BGM_THEME2,
+        },
+        Difficulty
+        {   size: IVec2::new( 41, 41 ),
+            generators: &[ Generator::MazeA1, Generator::MazeA2 ],
+            brightness: 0.6,
+            doors     : 1,
+            teleports : 2,
+            dark_zones: 1,
+            spinners  : 3,
+            pits      : 4,
+            monsters  : 5,
+            monster   : combat::Status { hp: 22, max_hp: 22, attack: 9, defense: 4 },
+            reward    : ( 13, 12 ),
+            events    : ASSETS_DATA_EVENTS_STAGE4,
+            bgm       : ASSETS_SOUND_BGM_THEME2,
+        },
+        Difficulty
+        {   size: IVec2::new( MAP_GRIDS_WIDTH, MAP_GRIDS_HEIGHT ),
+            generators: &[ Generator::MazeA2 ],
+            brightness: 0.45,
+            doors     : 2,
+            teleports : 3,
+            dark_zones: 2,
+            spinners  : 4,
+            pits      : 6,
+            monsters  : 8,
+            monster   : combat::Status { hp: 30, max_hp: 30, attack: 11, defense: 5 },
+            reward    : ( 18, 16 ),
+            events    : ASSETS_DATA_EVENTS_STAGE5,
+            bgm       : ASSETS_SOUND_BGM_THEME3,
+        },
+    ]
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ゲーム開始時にステージ１へ戻す
+pub fn reset_stage( mut stage: ResMut<Stage> )
+{   *stage = Stage::default();
+}
+
+//ステージの難易度に合わせてライトの明るさを変える
+pub fn apply_lighting
+(   mut qry_light: Query<&mut DirectionalLight>,
+    stage: Res<Stage>,
+)
+{   let illuminance = LIGHT3D_BRIGHTNESS * stage.difficulty().brightness;
+    qry_light.for_each_mut( | mut light | light.illuminance = illuminance );
+}
+
+//ステージのテーマのBGMを鳴らす（同じテーマが続けば鳴らし直さない）
+pub fn play_bgm
+(   mut evt_bgm: EventWriter<sound::PlayBgm>,
+    stage: Res<Stage>,
+)
+{   evt_bgm.send( sound::PlayBgm ( Some ( stage.difficulty().bgm ) ) );
+}
+
+//ゴールに着いたら記録を残して次のステージへ進む
+#[allow(clippy::too_many_arguments)]
+pub fn check_goal
+(   mut stage: ResMut<Stage>,
+    mut next_state: ResMut<NextState<MyState>>,
+    mut record: ResMut<hud::PlayRecord>,
+    player: Res<player::Player>,
+    map: Res<map::Map>,
+    recorder: Res<replay::Recorder>,
+    opt_playback: Option<Res<replay::Playback>>,
+    mut evt_sfx: EventWriter<sound::PlaySfx>,
+)
+{   //移動の途中ならゴールのマスに入り切っていない
+    if ! player.is_stop() || player.position != map.goal { return }
+    if map.goal == map.start { return } //袋小路がなくゴールを決められなかった
+
+    //リプレイの再生中は記録しない
+    if opt_playback.is_none()
+    {   scores::record
+        (   scores::ScoreEntry
+            {   seed    : stage.seed,
+                stage   : stage.number,
+                map_seed: stage.map_seed(),
+                time    : record.stage_elapsed,
+                steps   : record.stage_steps,
+                chests  : map.opened_chests().len(),
+            }
+        );
+        ghost::save_if_best( stage.map_seed(), record.stage_elapsed, stage.number, &recorder );
+    }
+    record.reset_stage();
+    evt_sfx.send( sound::PlaySfx ( sound::Sfx::StageClear ) );
+
+    stage.number += 1;
+    next_state.set( MyState::StageStart );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
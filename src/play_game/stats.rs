@@ -0,0 +1,278 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//持ち物（同じアイテムはまとめて持つ）
+#[derive( Clone, Serialize, Deserialize )]
+pub struct ItemStack
+{   pub id   : String, //アイテムのid
+    pub count: u32,    //個数
+}
+
+//Playerの能力値と持ち物のResource
+#[derive( Resource, Clone, Serialize, Deserialize )]
+pub struct PlayerStats
+{   pub status   : combat::Status, //体力・攻撃力・防御力
+    pub mp       : i32,            //MP
+    pub max_mp   : i32,            //MPの最大値
+    pub level    : u32,            //レベル
+    pub exp      : u32,            //経験値（次のレベルまでの累積）
+    pub gold     : u32,            //お金
+    pub inventory: Vec<ItemStack>, //持ち物
+}
+
+impl Default for PlayerStats
+{   fn default() -> Self
+    {   Self
+        {   status   : PLAYER_STATUS_INIT,
+            mp       : PLAYER_MP_INIT,
+            max_mp   : PLAYER_MP_INIT,
+            level    : 1,
+            exp      : 0,
+            gold     : 0,
+            inventory: PLAYER_ITEMS_INIT.iter()
+                .map( | &( id, count ) | ItemStack { id: id.to_string(), count } )
+                .collect(),
+        }
+    }
+}
+
+impl PlayerStats
+{   //次のレベルに必要な経験値
+    pub fn next_exp( &self ) -> u32
+    {   self.level * EXP_PER_LEVEL
+    }
+
+    //経験値を得る（上がったレベルの数を返す）
+    pub fn gain_exp( &mut self, exp: u32 ) -> u32
+    {   self.exp += exp;
+
+        let mut level_up = 0;
+        while self.exp >= self.next_exp()
+        {   self.exp -= self.next_exp();
+            self.level += 1;
+            self.status.max_hp  += LEVEL_UP_HP;
+            self.status.attack  += LEVEL_UP_ATTACK;
+            self.status.defense += LEVEL_UP_DEFENSE;
+            self.max_mp += LEVEL_UP_MP;
+            self.status.hp = self.status.max_hp; //レベルが上がったら全快する
+            self.mp = self.max_mp;
+            level_up += 1;
+        }
+
+        level_up
+    }
+
+    //アイテムを一つ持ち物に加える
+    pub fn add_item( &mut self, id: &str )
+    {   match self.inventory.iter_mut().find( | stack | stack.id == id )
+        {   Some ( stack ) => stack.count += 1,
+            None => self.inventory.push( ItemStack { id: id.to_string(), count: 1 } ),
+        }
+    }
+
+    //アイテムを一つ持ち物から取り除く（持っていなければfalseを返す）
+    pub fn remove_item( &mut self, id: &str ) -> bool
+    {   let Some ( index ) = self.inventory.iter().position( | stack | stack.id == id ) else { return false };
+
+        self.inventory[ index ].count -= 1;
+        if self.inventory[ index ].count == 0 { self.inventory.remove( index ); }
+
+        true
+    }
+
+    //持ち物のアイテムを使えるか？（ダメージを与えるアイテムは戦闘中だけ、鍵は扉の前だけ）
+    pub fn can_use( &self, index: usize, table: &items::ItemTable, is_in_combat: bool ) -> bool
+    {   let Some ( stack ) = self.inventory.get( index ) else { return false };
+        let Some ( item ) = table.get( &stack.id ) else { return false };
+        match item.effect
+        {   items::ItemEffect::Heal ( _ ) => self.status.hp < self.status.max_hp, //満タンなら使わない
+            items::ItemEffect::RestoreMp ( _ ) => self.mp < self.max_mp,
+            items::ItemEffect::Damage ( _ ) => is_in_combat,
+            items::ItemEffect::Key => false,
+        }
+    }
+
+    //持ち物のアイテムを使う（使えたら定義を返す。ダメージは呼び出し側で与える）
+    pub fn use_item( &mut self, index: usize, table: &items::ItemTable, is_in_combat: bool ) -> Option<items::ItemDef>
+    {   if ! self.can_use( index, table, is_in_combat ) { return None }
+        let item = table.get( &self.inventory[ index ].id )?.clone();
+
+        match item.effect
+        {   items::ItemEffect::Heal ( n ) =>
+                self.status.hp = ( self.status.hp + n ).min( self.status.max_hp ),
+            items::ItemEffect::RestoreMp ( n ) =>
+                self.mp = ( self.mp + n ).min( self.max_mp ),
+            items::ItemEffect::Damage ( _ ) | items::ItemEffect::Key => (),
+        }
+
+        //使い切ったら持ち物から消す
+        self.remove_item( &item.id );
+
+        Some ( item )
+    }
+
+    //持ち物をメニューにする（アイテム名と個数、最後に[BACK]）
+    pub fn item_menu( &self, table: &items::ItemTable, is_in_combat: bool ) -> menu::Menu
+    {   let mut items = Vec::new();
+        let mut labels = Vec::new();
+        let mut disabled = Vec::new();
+        for ( i, stack ) in self.inventory.iter().enumerate()
+        {   let name = table.get( &stack.id ).map_or( stack.id.as_str(), | item | item.name.as_str() );
+            items.push( menu::MenuItem::Use ( i ) );
+            labels.push( format!( "{name:<10}x{:<2}", stack.count ) );
+            if ! self.can_use( i, table, is_in_combat ) { disabled.push( menu::MenuItem::Use ( i ) ) }
+        }
+        items.push( menu::MenuItem::Back );
+        labels.push( format!( "{:<13}", menu::MenuItem::Back.label() ) );
+
+        menu::Menu::with_labels( &items, labels, &disabled )
+    }
+}
+
+//能力値の設定
+const PLAYER_STATUS_INIT: combat::Status = combat::Status { hp: 30, max_hp: 30, attack: 6, defense: 3 };
+const PLAYER_MP_INIT    : i32 = 10;
+const PLAYER_ITEMS_INIT : [ ( &str, u32 ); 1 ] = [ ( "potion", 2 ) ]; //最初の持ち物
+const EXP_PER_LEVEL     : u32 = 10; //レベル×これが次のレベルに必要な経験値
+const LEVEL_UP_HP       : i32 = 5;
+const LEVEL_UP_MP       : i32 = 2;
+const LEVEL_UP_ATTACK   : i32 = 1;
+const LEVEL_UP_DEFENSE  : i32 = 1;
+
+//持ち物画面の設定
+const KEY_INVENTORY: KeyCode = KeyCode::I;
+
+counted_array!
+(   const TEXT_INVENTORY: [ init_app::MessageSect; _ ] =
+    [   ( "ITEMS", ASSETS_FONT_ORBITRON_BLACK, PIXELS_PER_GRID * 2.0, Color::SILVER ),
+    ]
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ゲーム開始時に能力値と持ち物を初期化する
+pub fn reset_stats( mut stats: ResMut<PlayerStats> )
+{   *stats = PlayerStats::default();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//[I]キーで持ち物画面を開く
+pub fn catch_input_keyboard
+(   mut next_state: ResMut<NextState<MyState>>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   //ポーズなど他の遷移が先に決まっていたら開かない
+    if next_state.0.is_some() { return }
+
+    if inkey.just_pressed( KEY_INVENTORY ) { next_state.set( MyState::Inventory ) }
+}
+
+//持ち物画面の本文（能力値）
+fn stats_lines( stats: &PlayerStats ) -> Vec<String>
+{   let status = &stats.status;
+    vec!
+    [   format!( "LV {:>3}    EXP {:>4}/{:<4}", stats.level, stats.exp, stats.next_exp() ),
+        format!( "HP {:>3}/{:<3} MP {:>3}/{:<3}", status.hp, status.max_hp, stats.mp, stats.max_mp ),
+        format!( "AT {:>3}    DF {:>3}  G {:>5}", status.attack, status.defense, stats.gold ),
+    ]
+}
+
+//持ち物画面をspawnする
+fn spawn_inventory_overlay
+(   cmds: &mut Commands,
+    asset_svr: &Res<AssetServer>,
+    stats: &PlayerStats,
+    table: &items::ItemTable,
+)
+{   let body = stats_lines( stats );
+    let menu = stats.item_menu( table, false );
+    menu::spawn_overlay( cmds, asset_svr, &TEXT_INVENTORY, &body, menu );
+}
+
+//持ち物画面をspawnする
+pub fn spawn_overlay
+(   stats: Res<PlayerStats>,
+    mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+    tables: Res<Assets<items::ItemTable>>,
+)
+{   let Some ( table ) = items::table( &asset_svr, &tables ) else { return };
+    spawn_inventory_overlay( &mut cmds, &asset_svr, &stats, table );
+}
+
+//決定された項目のアイテムを使う（[I]キーか[BACK]で迷路へ戻る）
+#[allow(clippy::too_many_arguments)]
+pub fn select_menu
+(   mut evt_selected: EventReader<menu::MenuSelected>,
+    qry_overlay: Query<Entity, With<menu::MenuOverlay>>,
+    mut stats: ResMut<PlayerStats>,
+    mut next_state: ResMut<NextState<MyState>>,
+    mut cmds: Commands,
+    mut recorder: ResMut<replay::Recorder>,
+    asset_svr: Res<AssetServer>,
+    tables: Res<Assets<items::ItemTable>>,
+    inkey: Res<Input<KeyCode>>,
+    stage: Res<stage::Stage>,
+    record: Res<hud::PlayRecord>,
+)
+{   if inkey.just_pressed( KEY_INVENTORY ) { next_state.set( MyState::MainLoop ); return }
+
+    let Some ( menu::MenuSelected ( item ) ) = evt_selected.iter().last() else { return };
+    let Some ( table ) = items::table( &asset_svr, &tables ) else { return };
+
+    match item
+    {   menu::MenuItem::Use ( index ) =>
+        {   if stats.use_item( *index, table, false ).is_none() { return }
+
+            //リプレイでは迷路の中で使ったことにして再現する
+            recorder.record( stage.number, record.stage_elapsed, replay::PlayerCommand::Select ( *item ) );
+
+            //持ち物が変わったので作り直す
+            qry_overlay.for_each( | id | cmds.entity( id ).despawn_recursive() );
+            spawn_inventory_overlay( &mut cmds, &asset_svr, &stats, table );
+        }
+        menu::MenuItem::Back => next_state.set( MyState::MainLoop ),
+        _ => (),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests
+{   use super::*;
+
+    //必要な経験値に届かなければレベルは変わらない
+    #[test]
+    fn gain_exp_below_next_level()
+    {   let mut stats = PlayerStats::default();
+        assert_eq!( stats.gain_exp( EXP_PER_LEVEL - 1 ), 0 );
+        assert_eq!( stats.level, 1 );
+        assert_eq!( stats.exp, EXP_PER_LEVEL - 1 );
+    }
+
+    //一度に何レベルも上がり、余りは次のレベルへ持ち越して全快する
+    #[test]
+    fn gain_exp_multiple_levels()
+    {   let mut stats = PlayerStats::default();
+        stats.status.hp = 1;
+        stats.mp = 0;
+
+        //レベル1→2に10、2→3に20、余り5
+        assert_eq!( stats.gain_exp( EXP_PER_LEVEL * 3 + 5 ), 2 );
+        assert_eq!( stats.level, 3 );
+        assert_eq!( stats.exp, 5 );
+        assert_eq!( stats.status.max_hp, PLAYER_STATUS_INIT.max_hp + LEVEL_UP_HP * 2 );
+        assert_eq!( stats.status.attack, PLAYER_STATUS_INIT.attack + LEVEL_UP_ATTACK * 2 );
+        assert_eq!( stats.status.defense, PLAYER_STATUS_INIT.defense + LEVEL_UP_DEFENSE * 2 );
+        assert_eq!( stats.status.hp, stats.status.max_hp );
+        assert_eq!( stats.max_mp, PLAYER_MP_INIT + LEVEL_UP_MP * 2 );
+        assert_eq!( stats.mp, stats.max_mp );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -0,0 +1,104 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//罠と仕掛けの設定
+const PIT_DAMAGE       : i32 = 5; //落とし穴のダメージ
+const SPINNER_SEED_SALT: u64 = 0x5350_494E_4E45_5253; //回転床の向きの乱数を作る
+
+//進み終えたマスの罠と仕掛けの効果
+#[derive( Default )]
+pub struct TrapEffect
+{   pub damage: i32,           //落とし穴のダメージ
+    pub turn  : Option<News>,  //回転床で変わった向き
+    pub warp  : Option<IVec2>, //テレポーターの飛ばし先
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//進み終えたマスの罠と仕掛けの効果を決める（Playerとゴーストで同じ結果になる）
+//（回転床の向きは迷路の種・マス・歩数で決まるので、CONTINUEやリプレイでも変わらない）
+pub fn resolve( map: &map::Map, cell: IVec2, map_seed: u64, stage_steps: u32 ) -> TrapEffect
+{   let mut effect = TrapEffect::default();
+
+    //落とし穴：ダメージを受ける
+    if map.is_pit( cell ) { effect.damage = PIT_DAMAGE }
+
+    //回転床：向きがでたらめに変わる
+    if map.is_spinner( cell )
+    {   let salt = SPINNER_SEED_SALT ^ ( ( cell.x as u64 ) << 32 | cell.y as u32 as u64 );
+        let mut rng = StdRng::seed_from_u64( map_seed ^ salt ^ stage_steps as u64 );
+        effect.turn = Some ( NEWS[ rng.gen_range( 0..NEWS.len() ) ] );
+    }
+
+    //テレポーター：つながったマスへ飛ばされる
+    if map.is_teleporter( cell ) { effect.warp = map.teleport_destination( cell ) }
+
+    effect
+}
+
+//一マス進み終えたマスの罠と仕掛けを発動する
+#[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments)]
+pub fn trigger_traps
+(   mut evt_step: EventReader<player::StepFinished>,
+    mut param_set: ParamSet
+    <(  Query<&mut Transform, With<player::PlayerEntity>>,
+        Query<&mut Transform, With<player::FigureHead>>,
+        Query<&mut Transform, With<minimap::MinimapPlayer>>,
+    )>,
+    mut player: ResMut<player::Player>,
+    mut map: ResMut<map::Map>,
+    mut stats: ResMut<stats::PlayerStats>,
+    mut auto_walk: ResMut<auto_walk::AutoWalk>,
+    mut next_state: ResMut<NextState<MyState>>,
+    record: Res<hud::PlayRecord>,
+    stage: Res<stage::Stage>,
+)
+{   for &player::StepFinished ( cell ) in evt_step.iter()
+    {   //暗闇（ミニマップを隠すのはminimap::hide_in_dark_zone()）
+        if map.is_darkzone( cell ) { map.discover( cell ); }
+
+        let effect = resolve( &map, cell, stage.map_seed(), record.stage_steps );
+
+        //落とし穴：ダメージを受ける
+        if effect.damage > 0
+        {   map.discover( cell );
+            let status = &mut stats.status;
+            status.hp -= effect.damage;
+            if status.hp <= 0
+            {   status.hp = 0;
+                next_state.set( MyState::GameOver );
+                return;
+            }
+        }
+
+        //回転床：向きがでたらめに変わる
+        if let Some ( direction ) = effect.turn
+        {   map.discover( cell );
+            player.direction = direction;
+            auto_walk.cancel();
+
+            //3Dの姿とミニマップの三角形の向きを合わせる
+            let rotation = player.direction.to_quat_y();
+            param_set.p1().for_each_mut( | mut figure | figure.rotation = rotation );
+            let rotation = player.direction.to_quat_z();
+            param_set.p2().for_each_mut( | mut triangle | triangle.rotation = rotation );
+        }
+
+        //テレポーター：つながったマスへ飛ばされる
+        //（ミニマップのカメラは、移動の終わりにminimap::move_camera()が合わせる）
+        if let Some ( dest ) = effect.warp
+        {   map.discover( cell );
+            player.position = dest;
+            auto_walk.cancel();
+
+            let translation = dest.to_3dxz();
+            param_set.p0().for_each_mut( | mut transform | transform.translation = translation );
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -54,6 +54,16 @@ pub const SCREEN_GRIDS_Y_RANGE: Range<i32> = 0..SCREEN_GRIDS_HEIGHT;
 
 ////////////////////////////////////////////////////////////////////////////////
 
+//セーブデータの保存先
+pub const STORAGE_APP_NAME    : &str = "maze3d";       //データディレクトリ名(WASMはキーの接頭辞)
+pub const STORAGE_KEY_SAVEDATA: &str = "save.ron";     //ゲームの途中経過
+pub const STORAGE_KEY_SCORES  : &str = "scores.ron";   //ステージクリアの記録
+pub const STORAGE_KEY_REPLAY  : &str = "replay.ron";   //最後のゲームの操作の記録
+pub const STORAGE_KEY_GHOSTS  : &str = "ghosts.ron";   //迷路毎のベストタイムの操作の記録
+pub const STORAGE_KEY_SETTINGS: &str = "settings.ron"; //音量の設定
+
+////////////////////////////////////////////////////////////////////////////////
+
 //ログレベル
 pub const LOG_LEVEL_DEV: &str = "warn,wgpu_hal=error"; //開発
 pub const LOG_LEVEL_REL: &str = "error"; //リリース
@@ -61,8 +71,10 @@ pub const LOG_LEVEL_REL: &str = "error"; //リリース
 ////////////////////////////////////////////////////////////////////////////////
 
 //Cameraのレンダリングの重なり
-pub const ORDER_CAMERA2D_DEFAULT: isize = 3; //2D デフォルトカメラが最上
-pub const ORDER_CAMERA2D_MINIMAP: isize = 2; //2D ミニマップ用カメラが2番目
+pub const ORDER_CAMERA2D_DEFAULT: isize = 5; //2D デフォルトカメラが最上
+pub const ORDER_CAMERA2D_MINIMAP: isize = 4; //2D ミニマップ用カメラが2番目
+pub const ORDER_CAMERA2D_FULLMAP: isize = 3; //2D 全体マップ用カメラ(3D viewportに重ねる)
+pub const ORDER_CAMERA3D_FREEFLY: isize = 2; //3D debug用フリーカメラ(Playerカメラに重ねる)
 pub const ORDER_CAMERA3D_PLAYER : isize = 1; //3D Playerカメラ(Fpp&Tpp)
 pub const ORDER_CAMERA3D_DEFAULT: isize = 0; //3D デフォルトカメラが最下
 
@@ -93,6 +105,24 @@ pub const ASSETS_SPRITE_KANI_DOTOWN: &str = "sprites/kani_DOTOWN.png";
 pub const ASSETS_FONT_ORBITRON_BLACK      : &str = "fonts/Orbitron-Black.ttf";
 pub const ASSETS_FONT_PRESSSTART2P_REGULAR: &str = "fonts/PressStart2P-Regular.ttf";
 
+//assets（データ）
+pub const ASSETS_DATA_ITEMS        : &str = "data/basic.items.ron";
+pub const ASSETS_DATA_EVENTS_STAGE1: &str = "data/stage1.events.ron";
+pub const ASSETS_DATA_EVENTS_STAGE2: &str = "data/stage2.events.ron";
+pub const ASSETS_DATA_EVENTS_STAGE3: &str = "data/stage3.events.ron";
+pub const ASSETS_DATA_EVENTS_STAGE4: &str = "data/stage4.events.ron";
+pub const ASSETS_DATA_EVENTS_STAGE5: &str = "data/stage5.events.ron";
+
+//assets（サウンド）
+pub const ASSETS_SOUND_SE_FOOTSTEP   : &str = "sounds/se_footstep.wav";
+pub const ASSETS_SOUND_SE_BUMP       : &str = "sounds/se_bump.wav";
+pub const ASSETS_SOUND_SE_TURN       : &str = "sounds/se_turn.wav";
+pub const ASSETS_SOUND_SE_CHEST_OPEN : &str = "sounds/se_chest_open.wav";
+pub const ASSETS_SOUND_SE_STAGE_CLEAR: &str = "sounds/se_stage_clear.wav";
+pub const ASSETS_SOUND_BGM_THEME1    : &str = "sounds/bgm_theme1.wav";
+pub const ASSETS_SOUND_BGM_THEME2    : &str = "sounds/bgm_theme2.wav";
+pub const ASSETS_SOUND_BGM_THEME3    : &str = "sounds/bgm_theme3.wav";
+
 //事前ロード対象
 counted_array!
 (   pub const PRELOAD_ASSETS: [ &str; _ ] =
@@ -101,6 +131,20 @@ counted_array!
         ASSETS_SPRITE_KANI_DOTOWN,
         ASSETS_FONT_ORBITRON_BLACK,
         ASSETS_FONT_PRESSSTART2P_REGULAR,
+        ASSETS_DATA_ITEMS,
+        ASSETS_DATA_EVENTS_STAGE1,
+        ASSETS_DATA_EVENTS_STAGE2,
+        ASSETS_DATA_EVENTS_STAGE3,
+        ASSETS_DATA_EVENTS_STAGE4,
+        ASSETS_DATA_EVENTS_STAGE5,
+        ASSETS_SOUND_SE_FOOTSTEP,
+        ASSETS_SOUND_SE_BUMP,
+        ASSETS_SOUND_SE_TURN,
+        ASSETS_SOUND_SE_CHEST_OPEN,
+        ASSETS_SOUND_SE_STAGE_CLEAR,
+        ASSETS_SOUND_BGM_THEME1,
+        ASSETS_SOUND_BGM_THEME2,
+        ASSETS_SOUND_BGM_THEME3,
     ]
 );
 
@@ -111,8 +155,13 @@ pub const DEPTH_SPRITE_DEBUG_GRID : f32 = 999.0; //重なりの最大値
 pub const DEPTH_SPRITE_KANI_DOTOWN: f32 = 900.0;
 pub const DEPTH_SPRITE_GAME_FRAME : f32 = 800.0;
 
-pub const DEPTH_SPRITE_MINIMAP_PLAYER: f32 = DEPTH_SPRITE_GAME_FRAME - 1.0;
-pub const DEPTH_SPRITE_MINIMAP       : f32 = DEPTH_SPRITE_GAME_FRAME - 2.0;
+pub const DEPTH_SPRITE_MINIMAP_PLAYER : f32 = DEPTH_SPRITE_GAME_FRAME - 1.0;
+pub const DEPTH_SPRITE_MINIMAP_MONSTER: f32 = DEPTH_SPRITE_GAME_FRAME - 1.1;
+pub const DEPTH_SPRITE_MINIMAP_GHOST  : f32 = DEPTH_SPRITE_GAME_FRAME - 1.2;
+pub const DEPTH_SPRITE_MINIMAP_MARKER : f32 = DEPTH_SPRITE_GAME_FRAME - 1.5;
+pub const DEPTH_SPRITE_MINIMAP_TRAIL  : f32 = DEPTH_SPRITE_GAME_FRAME - 1.8;
+pub const DEPTH_SPRITE_MINIMAP        : f32 = DEPTH_SPRITE_GAME_FRAME - 2.0;
+pub const DEPTH_SPRITE_FULLMAP_BG     : f32 = DEPTH_SPRITE_GAME_FRAME - 3.0;
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -126,6 +175,16 @@ pub const ORBIT_CAMERA_MIN_R    : f32 = 1.0;       //rの最小値
 pub const ORBIT_CAMERA_MAX_THETA: f32 = PI * 0.99; //Θの最大値(ラジアン)
 pub const ORBIT_CAMERA_MIN_THETA: f32 = PI * 0.51; //Θの最小値(ラジアン)
 
+//三人称視点カメラの設定
+pub const TPP_CAMERA_INIT_R    : f32 = 3.5;       //初期値
+pub const TPP_CAMERA_INIT_THETA: f32 = PI * 0.65; //初期値(ラジアン)
+pub const TPP_CAMERA_LAG_COEF  : f32 = 4.0;  //プレイヤーの向きへの追従の速さ(大きいほど速い)
+pub const TPP_CAMERA_BACK_COEF : f32 = 2.0;  //壁から離れた後、元の距離へ戻る速さ
+pub const TPP_CAMERA_RAY_STEP  : f32 = 0.05; //壁判定の刻み幅
+pub const TPP_CAMERA_WALL_GAP  : f32 = 0.2;  //壁とカメラの隙間
+pub const TPP_CAMERA_WALL_TOP  : f32 = 0.5;  //壁の上面の高さ(これより上は遮られない)
+pub const SECS_CAMERA_TRANSITION: f32 = 0.6; //一人称視点⇔三人称視点の切替にかける秒数
+
 //極座標カメラ操作時のマウスの感度調整
 pub const MOUSE_WHEEL_Y_COEF : f32 = 0.1;
 pub const MOUSE_MOTION_Y_COEF: f32 = 0.01;
@@ -141,17 +200,17 @@ pub static SCREEN_FRAME: Lazy<ScreenFrame> = Lazy::new
     {   let design = vec!
         [  //0123456789 123456789 123456789 123456789 12
             "###########################################", //0
-            "#                               ###########", //1
-            "#                               ###########", //2
-            "#                               ###########", //3
-            "#                               ###########", //4
-            "#                               ###########", //5
-            "#                               ###########", //6
-            "#                               ###########", //7
-            "#                               ###########", //8
-            "#                               ###########", //9
-            "#                               ###########", //10
-            "#                               ###########", //11
+            "#                               ####HUD####", //1
+            "#                               #         #", //2
+            "#                               #         #", //3
+            "#                               #         #", //4
+            "#                               #         #", //5
+            "#                               #         #", //6
+            "#                               #         #", //7
+            "#                               #         #", //8
+            "#                               #         #", //9
+            "#                               #         #", //10
+            "#                               #         #", //11
             "#                               ##miniMAP##", //12
             "#                               #         #", //13
             "#                               #         #", //14
@@ -179,7 +238,12 @@ pub static SCREEN_FRAME: Lazy<ScreenFrame> = Lazy::new
             size: IVec2::new(  9,  9 ),
         };
 
-        ScreenFrame { design, viewport, minimap }
+        //HUDの小窓の設定
+        let hud = HudInfo
+        {   zero: IVec2::new( 33, 2 ),
+        };
+
+        ScreenFrame { design, viewport, minimap, hud }
     }
 );
 
@@ -193,18 +257,10 @@ pub const GROUND_PLANE_COLOR  : Color = Color::MAROON; //地面の色
 
 ////////////////////////////////////////////////////////////////////////////////
 
-//マップ縦横幅(Grid)
+//マップ縦横幅の最大値(Grid)（ステージ毎の大きさは難易度表で決まる）
 pub const MAP_GRIDS_WIDTH : i32 = 51;
 pub const MAP_GRIDS_HEIGHT: i32 = 51;
 
-//マップのレンジ（外壁含む）
-pub const MAP_GRIDS_X_RANGE: Range<i32> = 0..MAP_GRIDS_WIDTH;
-pub const MAP_GRIDS_Y_RANGE: Range<i32> = 0..MAP_GRIDS_HEIGHT;
-
-//外壁を含まないレンジ
-pub const MAP_GRIDS_X_RANGE_INNER: Range<i32> = 1..MAP_GRIDS_WIDTH  - 1;
-pub const MAP_GRIDS_Y_RANGE_INNER: Range<i32> = 1..MAP_GRIDS_HEIGHT - 1;
-
 ////////////////////////////////////////////////////////////////////////////////
 
 //四方の配列
@@ -228,4 +228,93 @@ pub fn move_orbit_camera<T: Component>
 
 ////////////////////////////////////////////////////////////////////////////////
 
+//フリーカメラ(壁をすり抜ける)のComponent
+#[derive( Component, Default )]
+pub struct FreeFlyCamera
+{   yaw  : f32, //水平角度(ラジアン)
+    pitch: f32, //垂直角度(ラジアン)
+}
+
+//フリーカメラの設定
+const KEY_TOGGLE_FREE_FLY: KeyCode = KeyCode::F2;
+const FREE_FLY_SPEED     : f32 = 4.0;   //移動速度(マス/秒)
+const FREE_FLY_BOOST     : f32 = 4.0;   //[Shift]キー押下時の倍率
+const FREE_FLY_MOUSE_COEF: f32 = 0.003; //マウスの感度
+const FREE_FLY_MAX_PITCH : f32 = FRAC_PI_2 * 0.99; //真上・真下の手前まで
+
+//フリーカメラをspawnする（初期状態は非アクティブ）
+pub fn spawn_free_fly_camera( mut cmds: Commands )
+{   cmds.spawn( ( Camera3dBundle::default(), FreeFlyCamera::default() ) )
+    .insert( Camera { order: ORDER_CAMERA3D_FREEFLY, is_active: false, ..default() } )
+    .insert( Camera3d { clear_color: CAMERA3D_BGCOLOR, ..default() } )
+    ;
+}
+
+//[F2]キーでフリーカメラの有効/無効を切り替える
+//有効にした時は、直前に描画していた3Dカメラの位置と向きから始める
+#[allow(clippy::type_complexity)]
+pub fn toggle_free_fly_camera
+(   mut qry_free_fly: Query<( &mut Camera, &mut Transform, &mut FreeFlyCamera )>,
+    qry_cameras: Query<( &Camera, &GlobalTransform ), ( With<Camera3d>, Without<FreeFlyCamera> )>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   if ! inkey.just_pressed( KEY_TOGGLE_FREE_FLY ) { return }
+    let Ok ( ( mut camera, mut transform, mut free_fly ) ) = qry_free_fly.get_single_mut() else { return };
+
+    camera.is_active = ! camera.is_active;
+    if ! camera.is_active { return }
+
+    //アクティブな3Dカメラのうち、最も上に描画されているものを引き継ぐ
+    let Some ( ( _, global ) ) = qry_cameras.iter()
+        .filter( | ( camera, _ ) | camera.is_active )
+        .max_by_key( | ( camera, _ ) | camera.order ) else { return };
+
+    *transform = global.compute_transform();
+    let ( yaw, pitch, _ ) = transform.rotation.to_euler( EulerRot::YXZ );
+    *free_fly = FreeFlyCamera { yaw, pitch };
+}
+
+//フリーカメラを移動する
+//[W][A][S][D]で前後左右、[Q][E]で下降上昇、右ボタンを押しながらマウスで見回す
+pub fn move_free_fly_camera
+(   mut qry_free_fly: Query<( &Camera, &mut Transform, &mut FreeFlyCamera )>,
+    mut evt_mouse_motion: EventReader<mouse::MouseMotion>,
+    mouse_button: Res<Input<MouseButton>>,
+    inkey: Res<Input<KeyCode>>,
+    time: Res<Time>,
+)
+{   //マウスの移動量（非アクティブでもイベントは読み捨てる）
+    let motion: Vec2 = evt_mouse_motion.iter().map( | event | event.delta ).sum();
+
+    let Ok ( ( camera, mut transform, mut free_fly ) ) = qry_free_fly.get_single_mut() else { return };
+    if ! camera.is_active { return } //アクティブでないなら更新しない
+
+    //見回す
+    if mouse_button.pressed( MouseButton::Right )
+    {   free_fly.yaw  -= motion.x * FREE_FLY_MOUSE_COEF;
+        free_fly.pitch = ( free_fly.pitch - motion.y * FREE_FLY_MOUSE_COEF )
+            .clamp( -FREE_FLY_MAX_PITCH, FREE_FLY_MAX_PITCH );
+    }
+    transform.rotation = Quat::from_euler( EulerRot::YXZ, free_fly.yaw, free_fly.pitch, 0.0 );
+
+    //移動する
+    let mut direction = Vec3::ZERO;
+    for keycode in inkey.get_pressed()
+    {   match keycode
+        {   KeyCode::W => direction += transform.forward(),
+            KeyCode::S => direction += transform.back(),
+            KeyCode::A => direction += transform.left(),
+            KeyCode::D => direction += transform.right(),
+            KeyCode::E => direction += Vec3::Y,
+            KeyCode::Q => direction += Vec3::NEG_Y,
+            _ => (),
+        }
+    }
+    let is_boost = inkey.any_pressed( [ KeyCode::ShiftLeft, KeyCode::ShiftRight ] );
+    let speed = FREE_FLY_SPEED * if is_boost { FREE_FLY_BOOST } else { 1.0 };
+    transform.translation += direction.normalize_or_zero() * speed * time.delta_seconds();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 //End of code.
\ No newline at end of file
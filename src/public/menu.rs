@@ -0,0 +1,216 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//メニューの項目
+#[derive( Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Debug )]
+pub enum MenuItem
+{   Start, Continue, Replay, Settings, Quit, //タイトル
+    Resume, GiveUp,                          //ポーズ
+    Retry, Title,                            //ゲームオーバー
+    Attack, Defend, Item, Flee, Ok,          //戦闘
+    Use ( usize ), Back,                     //アイテム（持ち物の添字）
+    Choice ( usize ),                        //メッセージの選択肢（選択肢の添字）
+    MasterVolume, SfxVolume, MusicVolume,    //設定
+}
+
+impl MenuItem
+{   //表示する文字列
+    pub fn label( &self ) -> &'static str
+    {   match self
+        {   MenuItem::Start        => "START",
+            MenuItem::Continue     => "CONTINUE",
+            MenuItem::Replay       => "REPLAY",
+            MenuItem::Settings     => "SETTINGS",
+            MenuItem::Quit         => "QUIT",
+            MenuItem::Resume       => "RESUME",
+            MenuItem::GiveUp       => "GIVE UP",
+            MenuItem::Retry        => "RETRY",
+            MenuItem::Title        => "TITLE",
+            MenuItem::Attack       => "ATTACK",
+            MenuItem::Defend       => "DEFEND",
+            MenuItem::Item         => "ITEM",
+            MenuItem::Flee         => "FLEE",
+            MenuItem::Ok           => "OK",
+            MenuItem::Use ( _ )    => "USE",
+            MenuItem::Back         => "BACK",
+            MenuItem::Choice ( _ ) => "CHOICE",
+            MenuItem::MasterVolume => "MASTER",
+            MenuItem::SfxVolume    => "SFX",
+            MenuItem::MusicVolume  => "MUSIC",
+        }
+    }
+}
+
+//メニューのComponent
+#[derive( Component )]
+pub struct Menu
+{   pub items   : Vec<MenuItem>,     //項目(上から順)
+    pub labels  : Vec<String>,       //項目の表示文字列
+    pub disabled: HashSet<MenuItem>, //選べない項目
+    pub cursor  : usize,             //選択中の項目の添字
+}
+
+impl Menu
+{   //最初の選べる項目にカーソルを置いて作る
+    pub fn new( items: &[ MenuItem ], disabled: &[ MenuItem ] ) -> Self
+    {   let labels = items.iter().map( | item | item.label().to_string() ).collect();
+        Self::with_labels( items, labels, disabled )
+    }
+
+    //表示文字列を指定して作る（アイテム名のように中身で変わる項目）
+    pub fn with_labels( items: &[ MenuItem ], labels: Vec<String>, disabled: &[ MenuItem ] ) -> Self
+    {   let items = items.to_vec();
+        let disabled: HashSet<_> = disabled.iter().copied().collect();
+        let cursor = items.iter().position( | item | ! disabled.contains( item ) ).unwrap_or( 0 );
+        Self { items, labels, disabled, cursor }
+    }
+}
+
+//メニューの項目が決定されたことを通知するEvent
+#[derive( Event )]
+pub struct MenuSelected ( pub MenuItem );
+
+//メニュー画面(背景とテキスト)の親のComponent
+#[derive( Component )]
+pub struct MenuOverlay;
+
+//メニュー画面の本文のComponent（一行が一つのTextSectionになる）
+#[derive( Component )]
+pub struct MenuBody;
+
+//メニューの設定
+const KEY_MENU_UP     : KeyCode = KeyCode::Up;
+const KEY_MENU_DOWN   : KeyCode = KeyCode::Down;
+const KEY_MENU_CONFIRM: KeyCode = KeyCode::Return;
+const COLOR_MENU_OVERLAY_BG: Color = Color::rgba( 0.0, 0.0, 0.0, 0.7 );
+const COLOR_MENU_ITEM      : Color = Color::SILVER;
+const COLOR_MENU_CURSOR    : Color = Color::YELLOW;
+const COLOR_MENU_DISABLED  : Color = Color::DARK_GRAY;
+const FONT_SIZE_MENU_ITEM  : f32 = PIXELS_PER_GRID;
+const COLOR_MENU_BODY      : Color = Color::SILVER;
+const FONT_SIZE_MENU_BODY  : f32 = PIXELS_PER_GRID * 0.5;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//3D viewportに重ねてメニュー画面をspawnする（見出し、本文、メニューの順に並べる。本文は空でもよい）
+pub fn spawn_overlay
+(   cmds: &mut Commands,
+    asset_svr: &Res<AssetServer>,
+    heading: &[ init_app::MessageSect ],
+    body: &[ String ],
+    menu: Menu,
+) -> Entity
+{   //半透明の背景
+    let ViewPortInfo { origin, size } = &SCREEN_FRAME.viewport;
+    let style = Style
+    {   position_type  : PositionType::Absolute,
+        left           : Val::Px( origin.x ),
+        top            : Val::Px( origin.y ),
+        width          : Val::Px( size.x ),
+        height         : Val::Px( size.y ),
+        flex_direction : FlexDirection::Column,
+        justify_content: JustifyContent::Center,
+        align_items    : AlignItems::Center,
+        row_gap        : Val::Px( PIXELS_PER_GRID * 2.0 ),
+        ..default()
+    };
+    let background_color = BackgroundColor ( COLOR_MENU_OVERLAY_BG );
+    let overlay = NodeBundle { style, background_color, ..default() };
+
+    //見出しとメニューの項目
+    let mut heading = init_app::text_ui( heading, asset_svr );
+    heading.style.position_type = PositionType::Relative; //縦に並べる
+
+    let is_body_empty = body.is_empty();
+    let lines: Vec<_> = body.iter().map( | line | format!( "{line}\n" ) ).collect();
+    let sections: Vec<init_app::MessageSect> = lines.iter()
+        .map( | line | ( line.as_str(), ASSETS_FONT_PRESSSTART2P_REGULAR, FONT_SIZE_MENU_BODY, COLOR_MENU_BODY ) )
+        .collect();
+    let mut body = init_app::text_ui( &sections, asset_svr );
+    body.style.position_type = PositionType::Relative;
+
+    let lines: Vec<_> = menu.labels.iter().map( | label | format!( "{label}\n" ) ).collect();
+    let sections: Vec<init_app::MessageSect> = lines.iter()
+        .map( | line | ( line.as_str(), ASSETS_FONT_PRESSSTART2P_REGULAR, FONT_SIZE_MENU_ITEM, COLOR_MENU_ITEM ) )
+        .collect();
+    let mut items = init_app::text_ui( &sections, asset_svr );
+    items.style.position_type = PositionType::Relative;
+
+    cmds.spawn( ( overlay, MenuOverlay ) )
+    .with_children
+    (   | cmds |
+        {   cmds.spawn( heading );
+            if ! is_body_empty { cmds.spawn( ( body, MenuBody ) ); }
+            cmds.spawn( ( items, menu ) );
+        }
+    )
+    .id()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//[↑][↓]キーでカーソルを動かす（選べない項目は飛ばす）
+pub fn move_cursor
+(   mut qry_menu: Query<&mut Menu>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   let Ok ( mut menu ) = qry_menu.get_single_mut() else { return };
+
+    let len = menu.items.len();
+    let step = if inkey.just_pressed( KEY_MENU_UP ) { len - 1 }
+        else if inkey.just_pressed( KEY_MENU_DOWN ) { 1 }
+        else { return };
+
+    //一周するまで次の選べる項目を探す
+    let mut cursor = menu.cursor;
+    for _ in 0..len
+    {   cursor = ( cursor + step ) % len;
+        if ! menu.disabled.contains( &menu.items[ cursor ] )
+        {   menu.cursor = cursor;
+            break;
+        }
+    }
+}
+
+//[Enter]キーで項目を決定する（[Alt]＋[Enter]はフルスクリーン切替なので除く）
+pub fn confirm
+(   qry_menu: Query<&Menu>,
+    mut evt_selected: EventWriter<MenuSelected>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   let Ok ( menu ) = qry_menu.get_single() else { return };
+
+    let is_alt_pressed = inkey.any_pressed( [ KeyCode::AltLeft, KeyCode::AltRight ] );
+    if ! inkey.just_pressed( KEY_MENU_CONFIRM ) || is_alt_pressed { return }
+
+    let item = menu.items[ menu.cursor ];
+    if menu.disabled.contains( &item ) { return }
+    evt_selected.send( MenuSelected ( item ) );
+}
+
+//カーソルの位置と選べない項目に合わせてテキストを更新する
+pub fn update_text
+(   mut qry_menu: Query<( &mut Text, &Menu ), Changed<Menu>>,
+)
+{   let Ok ( ( mut text, menu ) ) = qry_menu.get_single_mut() else { return };
+
+    let items = menu.items.iter().zip( menu.labels.iter() );
+    for ( i, ( section, ( item, label ) ) ) in text.sections.iter_mut().zip( items ).enumerate()
+    {   let ( value, color ) = if menu.disabled.contains( item )
+        {   ( format!( "  {label}  \n" ), COLOR_MENU_DISABLED )
+        }
+        else if i == menu.cursor
+        {   ( format!( "> {label} <\n" ), COLOR_MENU_CURSOR )
+        }
+        else
+        {   ( format!( "  {label}  \n" ), COLOR_MENU_ITEM )
+        };
+        section.value = value;
+        section.style.color = color;
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -11,6 +11,15 @@ pub use types::*;
 //ユーティリティ
 pub mod misc;
 
+//セーブデータの保存先
+pub mod storage;
+
+//ハイスコア・ベストタイムの記録
+pub mod scores;
+
+//メニュー画面
+pub mod menu;
+
 //debug用
 pub mod debug;
 
@@ -0,0 +1,111 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ステージクリアの記録（ゲームの種とステージ数が同じなら同じ迷路なので比較できる）
+#[derive( Clone, Serialize, Deserialize )]
+pub struct ScoreEntry
+{   pub seed    : u64,      //ゲーム全体の乱数の種
+    pub stage   : i32,      //ステージ数
+    pub map_seed: u64,      //ステージの迷路の乱数の種
+    pub time    : Duration, //クリアまでの時間
+    pub steps   : u32,      //クリアまでの歩数
+    pub chests  : usize,    //開けた宝箱の数
+}
+
+//スコア表の設定
+const SCORES_MAX_ENTRIES    : usize = 500; //保存する記録の上限(古い種の記録から捨てる)
+const SCORES_PER_STAGE      : usize = 10;  //種とステージの組毎に残す記録の数(遅い記録から捨てる)
+const SCORES_RANKS_PER_STAGE: usize = 3;   //表にステージ毎に並べる順位の数
+const SCORES_TABLE_ROWS     : usize = 9;   //表に並べる行数
+
+////////////////////////////////////////////////////////////////////////////////
+
+//保存済みの記録を読み出す（無い・壊れている場合は空）
+//記録は追加した順に並んでいる
+pub fn load() -> Vec<ScoreEntry>
+{   let Some ( text ) = storage::load( STORAGE_KEY_SCORES ) else { return Vec::new() };
+    ron::from_str( &text ).unwrap_or_else
+    (   | e |
+        {   warn!( "Broken score data: {e}" );
+            Vec::new()
+        }
+    )
+}
+
+//記録を追加して保存する
+pub fn record( entry: ScoreEntry )
+{   let mut entries = load();
+    let key = ( entry.seed, entry.stage );
+    entries.push( entry );
+
+    //同じ種とステージの記録が上限を超えたら、一番遅い記録を捨てる
+    let same_key = | entry: &&ScoreEntry | ( entry.seed, entry.stage ) == key;
+    if entries.iter().filter( same_key ).count() > SCORES_PER_STAGE
+    {   let slowest = entries.iter().enumerate()
+            .filter( | ( _, entry ) | same_key( entry ) )
+            .max_by_key( | ( _, entry ) | ( entry.time, entry.steps ) )
+            .map( | ( index, _ ) | index );
+        if let Some ( index ) = slowest { entries.remove( index ); }
+    }
+
+    //全体の上限を超えたら古い記録から捨てる
+    let excess = entries.len().saturating_sub( SCORES_MAX_ENTRIES );
+    entries.drain( ..excess );
+
+    let result = ron::to_string( &entries ).map_err( | e | e.to_string() )
+        .and_then( | text | storage::save( STORAGE_KEY_SCORES, &text ) );
+    if let Err ( e ) = result { warn!( "Failed to save the scores: {e}" ) }
+}
+
+//最後に記録した種（記録がなければNone）
+pub fn latest_seed() -> Option<u64>
+{   load().last().map( | entry | entry.seed )
+}
+
+//指定した種の記録を、ステージ毎に速い順で順位表の行にする
+//（同じ種なら同じ迷路なので、種を混ぜずに比べる）
+pub fn table_lines( seed: u64 ) -> Vec<String>
+{   let mut entries: Vec<_> = load().into_iter().filter( | entry | entry.seed == seed ).collect();
+
+    //ステージ順、同じステージなら速い順（歩数の少ない順）に並べる
+    entries.sort_by_key( | entry | ( entry.stage, entry.time, entry.steps ) );
+
+    //等幅フォントで中央揃えにするので、全部の行を同じ長さにする
+    let header = format!( "{:>5}  {:>4}  {:>8}  {:>4}  {:>5}", "STAGE", "RANK", "TIME", "STEP", "CHEST" );
+    let width = header.len();
+    let mut lines = vec![ header ];
+    if entries.is_empty()
+    {   lines.push( format!( "{:^width$}", "NO RECORDS YET" ) );
+    }
+
+    //ステージ毎に上位だけを並べる
+    let mut rank = 0;
+    let mut last_stage = None;
+    for entry in entries
+    {   if last_stage != Some ( entry.stage ) { rank = 0 }
+        last_stage = Some ( entry.stage );
+        rank += 1;
+        if rank > SCORES_RANKS_PER_STAGE { continue }
+        if lines.len() > SCORES_TABLE_ROWS { break }
+
+        lines.push
+        (   format!
+            (   "{:>5}  {:>4}  {}  {:>4}  {:>5}",
+                entry.stage, rank, format_time( entry.time ), entry.steps, entry.chests
+            )
+        );
+    }
+
+    lines
+}
+
+//時間を「分:秒.1/100秒」の文字列にする
+pub fn format_time( time: Duration ) -> String
+{   let centis = time.as_millis() / 10;
+    format!( "{:02}:{:02}.{:02}", centis / 6000, centis / 100 % 60, centis % 100 )
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -0,0 +1,83 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//キーに対応する文字列を保存する
+//ネイティブはユーザーのデータディレクトリのファイル、WASMはブラウザのlocalStorageに保存する
+pub fn save( key: &str, text: &str ) -> Result<(), String>
+{   #[cfg( not( target_arch = "wasm32" ) )]
+    {   let dir = data_dir().ok_or( "No data directory." )?;
+        std::fs::create_dir_all( &dir ).map_err( | e | e.to_string() )?;
+        std::fs::write( dir.join( key ), text ).map_err( | e | e.to_string() )
+    }
+
+    #[cfg( target_arch = "wasm32" )]
+    {   let storage = local_storage().ok_or( "No localStorage." )?;
+        storage.set_item( &storage_key( key ), text ).map_err( | e | format!( "{e:?}" ) )
+    }
+}
+
+//キーに対応する文字列を読み出す（無ければNone）
+pub fn load( key: &str ) -> Option<String>
+{   #[cfg( not( target_arch = "wasm32" ) )]
+    {   std::fs::read_to_string( data_dir()?.join( key ) ).ok()
+    }
+
+    #[cfg( target_arch = "wasm32" )]
+    {   local_storage()?.get_item( &storage_key( key ) ).ok()?
+    }
+}
+
+//キーに対応する文字列を削除する
+pub fn remove( key: &str )
+{   #[cfg( not( target_arch = "wasm32" ) )]
+    if let Some ( dir ) = data_dir()
+    {   let _ = std::fs::remove_file( dir.join( key ) );
+    }
+
+    #[cfg( target_arch = "wasm32" )]
+    if let Some ( storage ) = local_storage()
+    {   let _ = storage.remove_item( &storage_key( key ) );
+    }
+}
+
+//キーに対応する文字列があるか？
+pub fn exists( key: &str ) -> bool
+{   load( key ).is_some()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//ネイティブ：OS毎のユーザーのデータディレクトリ
+#[cfg( not( target_arch = "wasm32" ) )]
+fn data_dir() -> Option<std::path::PathBuf>
+{   use std::{ env, path::PathBuf };
+
+    let base = if cfg!( target_os = "windows" )
+    {   env::var_os( "APPDATA" ).map( PathBuf::from )
+    }
+    else if cfg!( target_os = "macos" )
+    {   env::var_os( "HOME" ).map( | home | PathBuf::from( home ).join( "Library/Application Support" ) )
+    }
+    else
+    {   env::var_os( "XDG_DATA_HOME" ).map( PathBuf::from )
+        .or_else( || env::var_os( "HOME" ).map( | home | PathBuf::from( home ).join( ".local/share" ) ) )
+    };
+
+    base.map( | dir | dir.join( STORAGE_APP_NAME ) )
+}
+
+//WASM：ブラウザのlocalStorage（他のアプリと混ざらないようキーにアプリ名を付ける）
+#[cfg( target_arch = "wasm32" )]
+fn local_storage() -> Option<web_sys::Storage>
+{   web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg( target_arch = "wasm32" )]
+fn storage_key( key: &str ) -> String
+{   format!( "{STORAGE_APP_NAME}/{key}" )
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
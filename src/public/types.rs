@@ -81,21 +81,37 @@ impl Default for OrbitCamera
 pub enum MyState
 {   #[default] LoadAssets,
     InitApp,
+    Title,
+    Settings,
     GameStart,
+    StageStart,
     MainLoop,
+    Pause,
+    Combat,
+    Inventory,
+    Message,
+    GameOver,
 }
 
 //Stateの遷移に使うマーカー(not Resource)
+#[derive( Default )] pub struct StageStart;
 #[derive( Default )] pub struct MainLoop;
 
 //Stateの遷移に使うResouce
 #[derive( Resource )] pub struct AfterLoadAssetsTo <T: States> ( pub T );
 #[derive( Resource )] pub struct AfterInitAppTo    <T: States> ( pub T );
 
+//タイトルでCONTINUEが選ばれたことを示すResouce
+#[derive( Resource )] pub struct ContinueGame;
+
+//タイトルでREPLAYが選ばれたことを示すResouce
+#[derive( Resource )] pub struct WatchReplay;
+
 //Stateの遷移に使うTrait
 pub trait GotoState { fn next( &self ) -> MyState; }
 
 //Traitの実装
+impl GotoState for StageStart                 { fn next( &self ) -> MyState { MyState::StageStart } }
 impl GotoState for MainLoop                   { fn next( &self ) -> MyState { MyState::MainLoop } }
 impl GotoState for AfterLoadAssetsTo<MyState> { fn next( &self ) -> MyState { self.0 } }
 impl GotoState for AfterInitAppTo<MyState>    { fn next( &self ) -> MyState { self.0 } }
@@ -107,6 +123,7 @@ pub struct ScreenFrame<'a>
 {   pub design  : Vec<&'a str>,
     pub viewport: ViewPortInfo,
     pub minimap : MiniMapInfo,
+    pub hud     : HudInfo,
 }
 
 //3Dカメラの表示領域(viewport)の情報
@@ -121,10 +138,15 @@ pub struct MiniMapInfo
     pub size: IVec2,
 }
 
+//HUDの情報
+pub struct HudInfo
+{   pub zero: IVec2,
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 //四方を表す列挙型
-#[derive( Default, Clone, Copy, PartialEq, Eq, Hash, Debug )]
+#[derive( Default, Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize )]
 pub enum News { #[default] North, East, West, South }
 
 //IVec2 = IVec2 + News
@@ -0,0 +1,139 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//プラグインの設定
+pub struct Schedule;
+impl Plugin for Schedule
+{   fn build( &self, app: &mut App )
+    {   app
+        //設定画面を表示する
+        .add_systems( OnEnter ( MyState::Settings ), spawn_settings )
+
+        //[←][→]キーで音量を変え、BACKでタイトルへ戻る
+        .add_systems
+        (   Update,
+            (   change_volume,
+                select_menu,
+            )
+            .run_if( in_state( MyState::Settings ) )
+        )
+
+        //設定画面を削除して、設定を保存する
+        .add_systems
+        (   OnExit ( MyState::Settings ),
+            (   misc::despawn::<menu::MenuOverlay>,
+                save_settings,
+            )
+        )
+        ;
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//設定画面の設定
+const KEY_VOLUME_UP  : KeyCode = KeyCode::Right;
+const KEY_VOLUME_DOWN: KeyCode = KeyCode::Left;
+
+counted_array!
+(   const TEXT_SETTINGS: [ init_app::MessageSect; _ ] =
+    [   ( "SETTINGS", ASSETS_FONT_ORBITRON_BLACK, PIXELS_PER_GRID * 2.0, Color::TEAL ),
+    ]
+);
+
+counted_array!
+(   const SETTINGS_MENU: [ menu::MenuItem; _ ] =
+    [   menu::MenuItem::MasterVolume,
+        menu::MenuItem::SfxVolume,
+        menu::MenuItem::MusicVolume,
+        menu::MenuItem::Back,
+    ]
+);
+
+counted_array!
+(   const SETTINGS_HELP: [ &str; _ ] =
+    [   "[LEFT][RIGHT] VOLUME",
+    ]
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+//項目の表示文字列（音量は百分率で並べる）
+fn labels( volumes: &sound::Volumes ) -> Vec<String>
+{   SETTINGS_MENU.iter()
+    .map
+    (   | item |
+        match volume( volumes, *item )
+        {   Some ( volume ) => format!( "{:<6} {:>3}%", item.label(), ( volume * 100.0 ).round() as i32 ),
+            None            => item.label().to_string(),
+        }
+    )
+    .collect()
+}
+
+//項目に対応する音量
+fn volume( volumes: &sound::Volumes, item: menu::MenuItem ) -> Option<f32>
+{   match item
+    {   menu::MenuItem::MasterVolume => Some ( volumes.master ),
+        menu::MenuItem::SfxVolume    => Some ( volumes.sfx    ),
+        menu::MenuItem::MusicVolume  => Some ( volumes.music  ),
+        _ => None,
+    }
+}
+
+//設定画面をspawnする
+fn spawn_settings
+(   mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+    volumes: Res<sound::Volumes>,
+)
+{   let body: Vec<_> = SETTINGS_HELP.iter().map( | line | line.to_string() ).collect();
+    let menu = menu::Menu::with_labels( &SETTINGS_MENU, labels( &volumes ), &[] );
+    menu::spawn_overlay( &mut cmds, &asset_svr, &TEXT_SETTINGS, &body, menu );
+}
+
+//[←][→]キーでカーソルのある項目の音量を変える（効果音は試しに鳴らす）
+fn change_volume
+(   mut qry_menu: Query<&mut menu::Menu>,
+    mut volumes: ResMut<sound::Volumes>,
+    mut evt_sfx: EventWriter<sound::PlaySfx>,
+    inkey: Res<Input<KeyCode>>,
+)
+{   let Ok ( mut menu ) = qry_menu.get_single_mut() else { return };
+
+    let up = if inkey.just_pressed( KEY_VOLUME_UP ) { true }
+        else if inkey.just_pressed( KEY_VOLUME_DOWN ) { false }
+        else { return };
+
+    let item = menu.items[ menu.cursor ];
+    let volume = match item
+    {   menu::MenuItem::MasterVolume => &mut volumes.master,
+        menu::MenuItem::SfxVolume    => &mut volumes.sfx,
+        menu::MenuItem::MusicVolume  => &mut volumes.music,
+        _ => return,
+    };
+    sound::Volumes::step( volume, up );
+
+    if item != menu::MenuItem::MusicVolume { evt_sfx.send( sound::PlaySfx ( sound::Sfx::Footstep ) ) }
+    menu.labels = labels( &volumes );
+}
+
+//BACKでタイトルへ戻る
+fn select_menu
+(   mut evt_selected: EventReader<menu::MenuSelected>,
+    mut next_state: ResMut<NextState<MyState>>,
+)
+{   for menu::MenuSelected ( item ) in evt_selected.iter()
+    {   if *item == menu::MenuItem::Back { next_state.set( MyState::Title ) }
+    }
+}
+
+//設定を保存する
+fn save_settings( volumes: Res<sound::Volumes> )
+{   volumes.save();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -0,0 +1,195 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//プラグインの設定
+pub struct Schedule;
+impl Plugin for Schedule
+{   fn build( &self, app: &mut App )
+    {   app
+        //保存済みの音量設定を読み込む
+        .insert_resource( Volumes::load() )
+        .add_event::<PlaySfx>() //効果音を鳴らす指示
+        .add_event::<PlayBgm>() //BGMを切り替える指示
+
+        //Stateに関係なく鳴らす
+        .add_systems
+        (   Update,
+            (   play_sfx,          //効果音を鳴らす
+                play_bgm,          //BGMを切り替える
+                apply_bgm_volume,  //音量の変更を鳴っているBGMに反映する
+            )
+        )
+        ;
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//音量の設定
+const VOLUME_STEP: f32 = 0.1; //設定画面で一回に変える量
+
+//音量設定のResource（全体の音量と、効果音・BGMそれぞれの音量を掛けて鳴らす）
+#[derive( Resource, Clone, Copy, PartialEq, Serialize, Deserialize )]
+pub struct Volumes
+{   pub master: f32, //全体
+    pub sfx   : f32, //効果音
+    pub music : f32, //BGM
+}
+
+impl Default for Volumes
+{   fn default() -> Self
+    {   Self { master: 0.8, sfx: 0.8, music: 0.5 }
+    }
+}
+
+impl Volumes
+{   //保存済みの設定を読み込む（無いか壊れていれば初期値）
+    fn load() -> Self
+    {   storage::load( STORAGE_KEY_SETTINGS )
+            .and_then( | text | ron::from_str( &text ).ok() )
+            .unwrap_or_default()
+    }
+
+    //設定を保存する
+    pub fn save( &self )
+    {   let result = ron::to_string( self ).map_err( | e | e.to_string() )
+            .and_then( | text | storage::save( STORAGE_KEY_SETTINGS, &text ) );
+        if let Err ( e ) = result { warn!( "Failed to save the settings: {e}" ) }
+    }
+
+    //一段階上げ下げする（0.0～1.0）
+    pub fn step( volume: &mut f32, up: bool )
+    {   let step = if up { VOLUME_STEP } else { -VOLUME_STEP };
+        *volume = ( ( *volume + step ) / VOLUME_STEP ).round() * VOLUME_STEP;
+        *volume = volume.clamp( 0.0, 1.0 );
+    }
+
+    fn sfx_volume  ( &self ) -> f32 { self.master * self.sfx   }
+    fn music_volume( &self ) -> f32 { self.master * self.music }
+}
+
+//効果音の種類
+#[derive( Clone, Copy, PartialEq, Eq, Debug )]
+pub enum Sfx
+{   Footstep,   //一マス進んだ
+    Bump,       //壁にぶつかった
+    Turn,       //向きを変えた
+    ChestOpen,  //宝箱を開けた
+    StageClear, //ゴールに着いた
+}
+
+impl Sfx
+{   fn asset( &self ) -> &'static str
+    {   match self
+        {   Sfx::Footstep   => ASSETS_SOUND_SE_FOOTSTEP,
+            Sfx::Bump       => ASSETS_SOUND_SE_BUMP,
+            Sfx::Turn       => ASSETS_SOUND_SE_TURN,
+            Sfx::ChestOpen  => ASSETS_SOUND_SE_CHEST_OPEN,
+            Sfx::StageClear => ASSETS_SOUND_SE_STAGE_CLEAR,
+        }
+    }
+}
+
+//効果音を鳴らすEvent
+#[derive( Event )]
+pub struct PlaySfx ( pub Sfx );
+
+//BGMを切り替えるEvent（Noneなら止める）
+#[derive( Event )]
+pub struct PlayBgm ( pub Option<&'static str> );
+
+//鳴っているBGMのComponent（同じ曲なら最初から鳴らし直さない）
+#[derive( Component )]
+struct Bgm ( &'static str );
+
+////////////////////////////////////////////////////////////////////////////////
+
+//効果音を鳴らす（鳴り終わったらdespawnされる）
+fn play_sfx
+(   mut evt_sfx: EventReader<PlaySfx>,
+    mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+    volumes: Res<Volumes>,
+)
+{   for PlaySfx ( sfx ) in evt_sfx.iter()
+    {   let volume = Volume::new_relative( volumes.sfx_volume() );
+        let source = asset_svr.load( sfx.asset() );
+        cmds.spawn( AudioBundle { source, settings: PlaybackSettings::DESPAWN.with_volume( volume ) } );
+    }
+}
+
+//BGMを切り替える（最後の指示だけに従う）
+fn play_bgm
+(   mut evt_bgm: EventReader<PlayBgm>,
+    qry_bgm: Query<( Entity, &Bgm )>,
+    mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+    volumes: Res<Volumes>,
+)
+{   let Some ( PlayBgm ( request ) ) = evt_bgm.iter().last() else { return };
+
+    //同じ曲が鳴っていればそのまま
+    if qry_bgm.iter().any( | ( _, bgm ) | Some ( bgm.0 ) == *request ) { return }
+    qry_bgm.for_each( | ( id, _ ) | cmds.entity( id ).despawn() );
+
+    let Some ( file ) = request else { return };
+    let volume = Volume::new_relative( volumes.music_volume() );
+    let source = asset_svr.load( *file );
+    cmds.spawn( ( AudioBundle { source, settings: PlaybackSettings::LOOP.with_volume( volume ) }, Bgm ( file ) ) );
+}
+
+//音量が変わったら鳴っているBGMに反映する（効果音は次に鳴らす時から）
+fn apply_bgm_volume
+(   qry_sink: Query<&AudioSink, With<Bgm>>,
+    volumes: Res<Volumes>,
+)
+{   if ! volumes.is_changed() { return }
+
+    qry_sink.for_each( | sink | sink.set_volume( volumes.music_volume() ) );
+}
+
+//BGMを止める
+pub fn stop_bgm( mut evt_bgm: EventWriter<PlayBgm> )
+{   evt_bgm.send( PlayBgm ( None ) );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests
+{   use super::*;
+
+    //一段階ずつ上げ下げでき、0.0～1.0を超えない
+    #[test]
+    fn volume_step_clamps()
+    {   let mut volume = 0.95;
+        Volumes::step( &mut volume, true );
+        assert_eq!( volume, 1.0 );
+        Volumes::step( &mut volume, true );
+        assert_eq!( volume, 1.0 );
+
+        let mut volume = 0.05;
+        Volumes::step( &mut volume, false );
+        assert_eq!( volume, 0.0 );
+        Volumes::step( &mut volume, false );
+        assert_eq!( volume, 0.0 );
+    }
+
+    //上げ下げを繰り返しても誤差がたまらない（段階の倍数に丸める）
+    #[test]
+    fn volume_step_rounds_to_steps()
+    {   let mut volume = 0.5;
+        for _ in 0..3 { Volumes::step( &mut volume, false ) }
+        for _ in 0..3 { Volumes::step( &mut volume, true  ) }
+        assert_eq!( volume, 0.5 );
+
+        let mut volume = 0.0;
+        for _ in 0..7 { Volumes::step( &mut volume, true ) }
+        assert!( ( volume - 0.7 ).abs() < f32::EPSILON );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.
@@ -0,0 +1,98 @@
+use super::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+//プラグインの設定
+pub struct Schedule;
+impl Plugin for Schedule
+{   fn build( &self, app: &mut App )
+    {   app
+        //タイトル画面を表示する（BGMは止める）
+        .add_systems( OnEnter ( MyState::Title ), ( spawn_title, sound::stop_bgm ) )
+
+        //メニューの選択
+        .add_systems
+        (   Update,
+            select_menu.run_if( in_state( MyState::Title ) )
+        )
+
+        //タイトル画面を削除する
+        .add_systems( OnExit ( MyState::Title ), misc::despawn::<menu::MenuOverlay> )
+        ;
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//タイトルの設定
+counted_array!
+(   const TEXT_TITLE: [ init_app::MessageSect; _ ] =
+    [   ( "MAZE 3D", ASSETS_FONT_ORBITRON_BLACK, PIXELS_PER_GRID * 3.0, Color::TEAL ),
+    ]
+);
+
+counted_array!
+(   const TITLE_MENU: [ menu::MenuItem; _ ] =
+    [   menu::MenuItem::Start,
+        menu::MenuItem::Continue,
+        menu::MenuItem::Replay,
+        menu::MenuItem::Settings,
+        menu::MenuItem::Quit,
+    ]
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+//タイトル画面をspawnする
+fn spawn_title
+(   mut cmds: Commands,
+    asset_svr: Res<AssetServer>,
+)
+{   //データがない項目は選べない（WASMではアプリを終了できない）
+    let mut disabled = Vec::new();
+    if ! storage::exists( STORAGE_KEY_SAVEDATA ) { disabled.push( menu::MenuItem::Continue ) }
+    if ! storage::exists( STORAGE_KEY_REPLAY   ) { disabled.push( menu::MenuItem::Replay   ) }
+    if misc::WASM() { disabled.push( menu::MenuItem::Quit ) }
+
+    //最後に遊んだ種でのステージ毎の順位表（種が違えば迷路が違うので混ぜない）
+    let body = match scores::latest_seed()
+    {   Some ( seed ) =>
+        {   let mut body = vec![ format!( "BEST TIMES ON SEED {seed:016X}" ), String::new() ];
+            body.extend( scores::table_lines( seed ) );
+            body
+        }
+        None => vec![ "BEST TIMES".to_string(), String::new(), "NO RECORDS YET".to_string() ],
+    };
+
+    let menu = menu::Menu::new( &TITLE_MENU, &disabled );
+    menu::spawn_overlay( &mut cmds, &asset_svr, &TEXT_TITLE, &body, menu );
+}
+
+//決定された項目に応じて遷移する
+fn select_menu
+(   mut evt_selected: EventReader<menu::MenuSelected>,
+    mut next_state: ResMut<NextState<MyState>>,
+    mut evt_app_exit: EventWriter<AppExit>,
+    mut cmds: Commands,
+)
+{   for menu::MenuSelected ( item ) in evt_selected.iter()
+    {   match item
+        {   menu::MenuItem::Start => next_state.set( MyState::GameStart ),
+            menu::MenuItem::Continue =>
+            {   cmds.insert_resource( ContinueGame ); //セーブデータから再開する
+                next_state.set( MyState::GameStart );
+            }
+            menu::MenuItem::Replay =>
+            {   cmds.insert_resource( WatchReplay ); //最後のゲームを再生する
+                next_state.set( MyState::GameStart );
+            }
+            menu::MenuItem::Settings => next_state.set( MyState::Settings ),
+            menu::MenuItem::Quit  => evt_app_exit.send( AppExit ),
+            _ => (),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+//End of code.